@@ -1,15 +1,208 @@
+use std::fmt;
+
 /// Sigmoid activation function
 pub fn sigmoid(x: f32) -> f32 {
     1.0 / (1.0 + (-x).exp())
 }
 
+/// Slope used by `leaky_relu` when no other value is given.
+pub const DEFAULT_LEAKY_SLOPE: f32 = 0.01;
+
+/// sqrt(2 / pi), used by the tanh approximation of GELU.
+const GELU_SCALE: f32 = 0.797_884_6;
+const GELU_CUBIC: f32 = 0.044_715;
+
+/// Activation functions an `ActivationLayer` can apply, each paired with its
+/// derivative.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Activation {
+    Sigmoid,
+    Relu,
+    LeakyRelu(f32),
+    Tanh,
+    Softplus,
+    Gelu,
+    Identity,
+}
+
+impl Activation {
+    pub fn apply(&self, x: f32) -> f32 {
+        match *self {
+            Activation::Sigmoid => sigmoid(x),
+            Activation::Relu => x.max(0.0),
+            Activation::LeakyRelu(slope) => {
+                if x > 0.0 {
+                    x
+                } else {
+                    slope * x
+                }
+            }
+            Activation::Tanh => x.tanh(),
+            Activation::Softplus => {
+                // ln(1 + e^x) overflows for large x, where it equals x anyway.
+                if x > 20.0 {
+                    x
+                } else {
+                    x.exp().ln_1p()
+                }
+            }
+            Activation::Gelu => {
+                let inner = GELU_SCALE * (x + GELU_CUBIC * x * x * x);
+                0.5 * x * (1.0 + inner.tanh())
+            }
+            Activation::Identity => x,
+        }
+    }
+
+    /// Derivative at input `x`, where `y` is `apply(x)`. Sigmoid and tanh
+    /// are cheaper to differentiate from their output.
+    pub fn derivative(&self, x: f32, y: f32) -> f32 {
+        match *self {
+            Activation::Sigmoid => y * (1.0 - y),
+            Activation::Relu => {
+                if x > 0.0 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Activation::LeakyRelu(slope) => {
+                if x > 0.0 {
+                    1.0
+                } else {
+                    slope
+                }
+            }
+            Activation::Tanh => 1.0 - y * y,
+            Activation::Softplus => sigmoid(x),
+            Activation::Gelu => {
+                let inner = GELU_SCALE * (x + GELU_CUBIC * x * x * x);
+                let t = inner.tanh();
+                let d_inner = GELU_SCALE * (1.0 + 3.0 * GELU_CUBIC * x * x);
+                0.5 * (1.0 + t) + 0.5 * x * (1.0 - t * t) * d_inner
+            }
+            Activation::Identity => 1.0,
+        }
+    }
+
+    /// Name without parameters, e.g. `leaky_relu`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Activation::Sigmoid => "sigmoid",
+            Activation::Relu => "relu",
+            Activation::LeakyRelu(_) => "leaky_relu",
+            Activation::Tanh => "tanh",
+            Activation::Softplus => "softplus",
+            Activation::Gelu => "gelu",
+            Activation::Identity => "identity",
+        }
+    }
+
+    /// Parses the `Display` form. `leaky_relu` takes an optional slope
+    /// written as `leaky_relu:0.2`.
+    pub fn from_name(name: &str) -> Option<Activation> {
+        let (base, param) = match name.split_once(':') {
+            Some((base, param)) => (base, Some(param)),
+            None => (name, None),
+        };
+        let activation = match base {
+            "sigmoid" => Activation::Sigmoid,
+            "relu" => Activation::Relu,
+            "leaky_relu" => {
+                let slope = match param {
+                    Some(p) => p.parse().ok()?,
+                    None => DEFAULT_LEAKY_SLOPE,
+                };
+                return Some(Activation::LeakyRelu(slope));
+            }
+            "tanh" => Activation::Tanh,
+            "softplus" => Activation::Softplus,
+            "gelu" => Activation::Gelu,
+            "identity" => Activation::Identity,
+            _ => return None,
+        };
+        match param {
+            Some(_) => None,
+            None => Some(activation),
+        }
+    }
+}
+
+impl fmt::Display for Activation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Activation::LeakyRelu(slope) => write!(f, "{}:{}", self.name(), slope),
+            _ => write!(f, "{}", self.name()),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const ALL: [Activation; 7] = [
+        Activation::Sigmoid,
+        Activation::Relu,
+        Activation::LeakyRelu(DEFAULT_LEAKY_SLOPE),
+        Activation::Tanh,
+        Activation::Softplus,
+        Activation::Gelu,
+        Activation::Identity,
+    ];
+
     #[test]
     fn test_sigmoid() {
         let approx = sigmoid(0.0);
         assert!((approx - 0.5).abs() < 1e-5);
     }
+
+    #[test]
+    fn test_activation_values() {
+        assert_eq!(Activation::Relu.apply(-2.0), 0.0);
+        assert_eq!(Activation::Relu.apply(3.0), 3.0);
+        assert_eq!(Activation::LeakyRelu(0.1).apply(-2.0), -0.2);
+        assert_eq!(Activation::Identity.apply(-1.5), -1.5);
+        assert!((Activation::Tanh.apply(0.5) - 0.5f32.tanh()).abs() < 1e-6);
+        assert!((Activation::Softplus.apply(0.0) - 2f32.ln()).abs() < 1e-6);
+        assert_eq!(Activation::Softplus.apply(100.0), 100.0);
+        assert!((Activation::Gelu.apply(1.0) - 0.841_192).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_derivatives_match_finite_differences() {
+        let h = 1e-3;
+        let points = [-3.0, -1.2, -0.4, 0.3, 0.9, 2.5];
+        for activation in ALL {
+            for &x in &points {
+                let numeric = (activation.apply(x + h) - activation.apply(x - h)) / (2.0 * h);
+                let analytic = activation.derivative(x, activation.apply(x));
+                let err = (numeric - analytic).abs() / numeric.abs().max(analytic.abs()).max(1.0);
+                assert!(
+                    err < 1e-3,
+                    "{} at {}: analytic {} vs numeric {}",
+                    activation,
+                    x,
+                    analytic,
+                    numeric
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_activation_name_round_trip() {
+        for activation in ALL {
+            assert_eq!(
+                Activation::from_name(&activation.to_string()),
+                Some(activation)
+            );
+        }
+        assert_eq!(
+            Activation::from_name("leaky_relu:0.2"),
+            Some(Activation::LeakyRelu(0.2))
+        );
+        assert_eq!(Activation::from_name("unknown"), None);
+        assert_eq!(Activation::from_name("relu:1"), None);
+    }
 }
@@ -0,0 +1,122 @@
+//! Timings behind the `bench` subcommand: forward passes over all 1024
+//! numbers, one sample at a time and as one batch, with the blocked matmul
+//! and with the naive triple loop `Matrix::dot` used to be, and as one batch
+//! split across all cores.
+
+use crate::dataset::u16_to_bin_vector;
+use crate::layer::LayerEnum;
+use crate::matrix::Matrix;
+use crate::model::Model;
+use crate::parallel::{available_threads, predict_parallel};
+use std::time::{Duration, Instant};
+
+/// Architecture timed when none is given: wide enough that the matrix
+/// products dominate.
+pub const DEFAULT_BENCH_SPEC: &str = "10-d256-relu-d256-relu-d1-sigmoid";
+
+pub struct BenchResult {
+    pub name: &'static str,
+    /// Mean time of one pass over all 1024 samples.
+    pub per_pass: Duration,
+}
+
+/// Times each way of running `model` over every number `repeats` times.
+pub fn run_benchmarks(model: &Model, repeats: usize) -> Vec<BenchResult> {
+    let samples: Vec<Vec<f32>> = (0..=1023)
+        .map(|n| u16_to_bin_vector(n).iter().map(|&b| b as f32).collect())
+        .collect();
+    let batch = Matrix::from_vec(samples.clone());
+    let singles: Vec<Matrix> = samples
+        .into_iter()
+        .map(|s| Matrix::from_vec(vec![s]))
+        .collect();
+
+    let time = |name, pass: &dyn Fn(&Model) -> f32| {
+        // One untimed pass warms the caches.
+        let mut checksum = pass(model);
+        let start = Instant::now();
+        for _ in 0..repeats {
+            checksum += pass(model);
+        }
+        let per_pass = start.elapsed() / repeats.max(1) as u32;
+        // Using the outputs keeps the optimizer from dropping the work.
+        std::hint::black_box(checksum);
+        BenchResult { name, per_pass }
+    };
+
+    vec![
+        time("per sample, naive matmul", &|model| {
+            singles
+                .iter()
+                .map(|x| naive_forward(model, x).get(0, 0))
+                .sum()
+        }),
+        time("per sample, blocked matmul", &|model| {
+            singles.iter().map(|x| model.predict(x).get(0, 0)).sum()
+        }),
+        time("batched, naive matmul", &|model| {
+            naive_forward(model, &batch).get(0, 0)
+        }),
+        time("batched, blocked matmul", &|model| {
+            model.predict(&batch).get(0, 0)
+        }),
+        time("batched, all cores", &|model| {
+            predict_parallel(model, &batch, available_threads()).get(0, 0)
+        }),
+    ]
+}
+
+/// `Model::predict` with dense layers computed by `naive_dot`.
+fn naive_forward(model: &Model, input: &Matrix) -> Matrix {
+    let mut x = input.clone();
+    for layer in model.layers() {
+        x = match layer {
+            LayerEnum::Dense(d) => {
+                let mut out = naive_dot(&x, &d.weights);
+                out.broadcast_add_assign(&d.biases);
+                out
+            }
+            _ => layer.predict(&x),
+        };
+    }
+    x
+}
+
+/// The original matmul: one bounds-checked `get` per product, with the
+/// inner loop striding down a column of `b`.
+fn naive_dot(a: &Matrix, b: &Matrix) -> Matrix {
+    let data = (0..a.rows())
+        .map(|i| {
+            (0..b.cols())
+                .map(|j| (0..a.cols()).map(|k| a.get(i, k) * b.get(k, j)).sum())
+                .collect()
+        })
+        .collect();
+    Matrix::from_vec(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spec::parse_spec;
+
+    #[test]
+    fn test_naive_forward_matches_model_forward() {
+        let model = parse_spec("10-d70-relu-d3-softmax", 1).unwrap();
+        let input = Matrix::from_vec(
+            (0..5u16)
+                .map(|n| {
+                    u16_to_bin_vector(n * 97)
+                        .iter()
+                        .map(|&b| b as f32)
+                        .collect()
+                })
+                .collect(),
+        );
+        let expected = model.predict(&input);
+        let actual = naive_forward(&model, &input);
+        for (a, b) in expected.as_slice().iter().zip(actual.as_slice()) {
+            assert!((a - b).abs() < 1e-6);
+        }
+    }
+}
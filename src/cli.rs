@@ -0,0 +1,883 @@
+//! Non-interactive subcommands, e.g. `train --epochs 20 --out model.txt`.
+//! Running the program without arguments starts the interactive menu instead.
+
+use crate::bench::{run_benchmarks, DEFAULT_BENCH_SPEC};
+use crate::dataset::{
+    generate_dataset, select, split_dataset, u16_to_bin_vector, SplitConfig, SplitStrategy, Task,
+    INPUT_BITS,
+};
+use crate::evaluation::{binary_scores, class_counts, pr_auc, roc_auc, BinaryMetrics};
+use crate::gradcheck::{check_gradients, DEFAULT_STEP};
+use crate::init::Initializer;
+use crate::interface;
+use crate::layer::LayerEnum;
+use crate::loss::{ClassWeights, Loss, DEFAULT_FOCAL_GAMMA};
+use crate::matrix::Matrix;
+use crate::model::{Model, DEFAULT_SEED};
+use crate::optimizer::OptimizerKind;
+use crate::regularization::Regularization;
+use crate::schedule::LrSchedule;
+use crate::spec::{default_spec, model_spec, parse_spec};
+use crate::storage::{load_model, save_model};
+use crate::train::{
+    append_logit_sigmoid, argmax, batch_to_matrices, check_batch_size, train_epochs, TrainConfig,
+};
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Exit status when a command fails while running, e.g. a model file cannot
+/// be read.
+const EXIT_FAILURE: i32 = 1;
+/// Exit status for invalid arguments.
+const EXIT_USAGE: i32 = 2;
+
+const USAGE: &str = "\
+Usage: simplest-artificial-intelligence [COMMAND] [OPTIONS]
+
+Without a command the interactive menu starts.
+
+Commands:
+  train        Train a model and save it
+  predict      Print the model's prediction for each number
+  evaluate     Print metrics on a data split
+  inspect      Print the architecture and size of a saved model
+  bench        Time forward passes over all 1024 numbers
+  gradcheck    Compare backpropagated gradients with finite differences
+  interactive  Start the interactive menu
+  help         Show this message
+
+Options:
+  --task NAME        primality, mod:K, prime_factor_count or
+                     smallest_prime_factor [default: primality]
+  --arch SPEC        Architecture for a new model, e.g. 10-d16-relu-d1-sigmoid
+  --model PATH       Model to read (train continues from it if given)
+  --out PATH         Where train saves the model [default: model.txt]
+  --epochs N         Training epochs [default: 10]
+  --lr RATE          Learning rate [default: depends on the optimizer]
+  --schedule NAME    Learning-rate schedule, with optional parameters:
+                     constant, step:EVERY:GAMMA, exponential:GAMMA,
+                     cosine:PERIOD:MULT (warm restarts), warmup:EPOCHS or
+                     plateau:FACTOR:PATIENCE [default: constant]
+  --batch N          Batch size, at least 2 for models with batch norm
+                     [default: 1; gradcheck: 8]
+  --threads N        Threads each training batch is split across [default: 1]
+  --l1 X             L1 penalty on dense weights [default: 0]
+  --l2 X             L2 penalty on dense weights, applied through the
+                     optimizer [default: 0]
+  --weight-decay X   Decoupled (AdamW-style) weight decay [default: 0]
+  --clip-value X     Clamp every gradient entry to [-X, X]
+  --clip-norm X      Rescale gradients to a global L2 norm of at most X
+  --optimizer NAME   sgd, momentum, nesterov, rmsprop or adam [default: sgd]
+  --loss NAME        mse, bce, bce_logits or focal for primality; other
+                     tasks always use cross_entropy [default: mse]
+  --class-weights W  Binary loss class weights for primality: balanced,
+                     none or NEG:POS [default: balanced]
+  --init NAME        Weight initializer for a new model: uniform,
+                     xavier_uniform, xavier_normal, he_uniform, he_normal,
+                     lecun, orthogonal or constant:X [default: uniform]
+  --bias X           Initial value of every bias of a new model [default: 0]
+  --seed N           Seed for weights, shuffling and the data split
+                     [default: the seed saved with --model, else 42]
+  --split NAME       random, stratified or range [default: stratified]
+  --set NAME         Split evaluate scores: train, validation, test or all
+                     [default: test]
+  --step H           Finite-difference step for gradcheck [default: 0.01]
+  --mode NAME        Whether gradcheck runs the model in train or eval mode
+                     [default: train]
+  --repeats N        Passes bench times per variant [default: 20]
+  --format NAME      text or json [default: text]
+
+predict reads numbers (0-1023) from its arguments, or from stdin if none
+are given.
+
+Exit status: 0 on success, 1 if the command failed, 2 for invalid arguments.";
+
+enum CliError {
+    Usage(String),
+    Failure(String),
+    /// `--help` or `-h` was given to a subcommand.
+    Help,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Text,
+    Json,
+}
+
+/// Flags given as `--name value` or `--name=value`, plus positional values.
+/// `--help` or `-h` in place of a flag asks for the usage text.
+struct Args {
+    flags: HashMap<String, String>,
+    positional: Vec<String>,
+}
+
+impl Args {
+    fn parse(args: &[String], allowed: &[&str]) -> Result<Args, CliError> {
+        let mut flags = HashMap::new();
+        let mut positional = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if arg == "--help" || arg == "-h" {
+                return Err(CliError::Help);
+            }
+            let Some(flag) = arg.strip_prefix("--") else {
+                positional.push(arg.clone());
+                continue;
+            };
+            let (name, value) = match flag.split_once('=') {
+                Some((name, value)) => (name, value.to_string()),
+                None => {
+                    let value = iter
+                        .next()
+                        .ok_or_else(|| usage(format!("--{} needs a value", flag)))?;
+                    (flag, value.clone())
+                }
+            };
+            if !allowed.contains(&name) {
+                return Err(usage(format!("unknown option --{}", name)));
+            }
+            flags.insert(name.to_string(), value);
+        }
+        Ok(Args { flags, positional })
+    }
+
+    fn value(&self, name: &str) -> Option<&str> {
+        self.flags.get(name).map(String::as_str)
+    }
+
+    fn number<T: std::str::FromStr>(&self, name: &str, default: T) -> Result<T, CliError> {
+        match self.value(name) {
+            Some(v) => v
+                .parse()
+                .map_err(|_| usage(format!("invalid value '{}' for --{}", v, name))),
+            None => Ok(default),
+        }
+    }
+
+    fn named<T>(
+        &self,
+        name: &str,
+        default: T,
+        parse: impl Fn(&str) -> Option<T>,
+    ) -> Result<T, CliError> {
+        match self.value(name) {
+            Some(v) => parse(v).ok_or_else(|| usage(format!("unknown {} '{}'", name, v))),
+            None => Ok(default),
+        }
+    }
+
+    fn format(&self) -> Result<Format, CliError> {
+        self.named("format", Format::Text, |name| match name {
+            "text" => Some(Format::Text),
+            "json" => Some(Format::Json),
+            _ => None,
+        })
+    }
+
+    fn task(&self) -> Result<Task, CliError> {
+        self.named("task", Task::Primality, Task::from_name)
+    }
+
+    fn split(&self, seed: u64) -> Result<SplitConfig, CliError> {
+        let defaults = SplitConfig::default();
+        Ok(SplitConfig {
+            strategy: self.named("split", defaults.strategy, SplitStrategy::from_name)?,
+            seed,
+            ..defaults
+        })
+    }
+
+    fn model_path(&self) -> Result<&str, CliError> {
+        self.value("model")
+            .ok_or_else(|| usage("--model is required".to_string()))
+    }
+}
+
+fn usage(message: String) -> CliError {
+    CliError::Usage(message)
+}
+
+/// Runs the subcommand in `args` (without the program name) and returns the
+/// process exit status.
+pub fn run(args: &[String]) -> i32 {
+    let (command, rest) = match args.split_first() {
+        Some((command, rest)) => (command.as_str(), rest),
+        None => ("interactive", args),
+    };
+    let result = match command {
+        "train" => train(rest),
+        "predict" => predict(rest),
+        "evaluate" => evaluate(rest),
+        "inspect" => inspect(rest),
+        "bench" => bench(rest),
+        "gradcheck" => gradcheck(rest),
+        "interactive" => {
+            interface::run_ui();
+            Ok(())
+        }
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(usage(format!("unknown command '{}'", command))),
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(CliError::Help) => {
+            println!("{}", USAGE);
+            0
+        }
+        Err(CliError::Usage(message)) => {
+            eprintln!("error: {}", message);
+            eprintln!("Run with --help for usage.");
+            EXIT_USAGE
+        }
+        Err(CliError::Failure(message)) => {
+            eprintln!("error: {}", message);
+            EXIT_FAILURE
+        }
+    }
+}
+
+fn read_model(path: &str) -> Result<Model, CliError> {
+    load_model(Path::new(path))
+        .map_err(|e| CliError::Failure(format!("could not load {}: {}", path, e)))
+}
+
+/// A model that cannot read the input bits is a usage error, like any other
+/// mismatch between the model and the command line.
+fn check_input_width(model: &Model) -> Result<(), CliError> {
+    if model.input_size() != INPUT_BITS {
+        return Err(usage(format!(
+            "the model takes {} input(s) but numbers have {} bits",
+            model.input_size(),
+            INPUT_BITS
+        )));
+    }
+    Ok(())
+}
+
+fn check_task(model: &Model, task: Task) -> Result<(), CliError> {
+    check_input_width(model)?;
+    if model.output_size() != task.num_classes() {
+        return Err(usage(format!(
+            "the model has {} output(s) but {} expects {}",
+            model.output_size(),
+            task.name(),
+            task.num_classes()
+        )));
+    }
+    Ok(())
+}
+
+/// The model read from `--model`, reseeded if `--seed` is given, or a new
+/// one built from `--arch` (default: the task's default network).
+fn model_from_args(args: &Args, task: Task) -> Result<Model, CliError> {
+    match (args.value("model"), args.value("arch")) {
+        (Some(_), Some(_)) => Err(usage("give --model or --arch, not both".to_string())),
+        (Some(path), None) => {
+            let mut model = read_model(path)?;
+            if args.value("seed").is_some() {
+                model.reseed(args.number("seed", DEFAULT_SEED)?);
+            }
+            Ok(model)
+        }
+        (None, arch) => parse_spec(
+            arch.unwrap_or(&default_spec(task)),
+            args.number("seed", DEFAULT_SEED)?,
+        )
+        .map_err(|e| usage(format!("invalid --arch: {}", e))),
+    }
+}
+
+/// Loss for `--loss`, with the binary losses weighted by
+/// `--class-weights`: balanced by default, as the interactive menu
+/// suggests.
+fn parse_loss(args: &Args, task: Task, labels: &[u8]) -> Result<Loss, CliError> {
+    if task.num_classes() > 1 {
+        if args.value("class-weights").is_some() {
+            return Err(usage(format!(
+                "--class-weights only applies to primality, not {}",
+                task.name()
+            )));
+        }
+        return match args.value("loss") {
+            None | Some("cross_entropy") => Ok(Loss::CategoricalCrossEntropy),
+            Some(name) => Err(usage(format!(
+                "{} only supports cross_entropy, not '{}'",
+                task.name(),
+                name
+            ))),
+        };
+    }
+    let weights = parse_class_weights(args, labels)?;
+    match args.value("loss").unwrap_or("mse") {
+        "mse" => Ok(Loss::MeanSquaredError),
+        "bce" => Ok(Loss::BinaryCrossEntropy {
+            weights,
+            from_logits: false,
+        }),
+        "bce_logits" => Ok(Loss::BinaryCrossEntropy {
+            weights,
+            from_logits: true,
+        }),
+        "focal" => Ok(Loss::Focal {
+            gamma: DEFAULT_FOCAL_GAMMA,
+            weights,
+        }),
+        name => Err(usage(format!("unknown loss '{}'", name))),
+    }
+}
+
+/// `--class-weights`: `balanced`, `none` or `NEGATIVE:POSITIVE`.
+fn parse_class_weights(args: &Args, labels: &[u8]) -> Result<ClassWeights, CliError> {
+    match args.value("class-weights").unwrap_or("balanced") {
+        "balanced" => Ok(ClassWeights::balanced(labels)),
+        "none" => Ok(ClassWeights::UNIFORM),
+        value => {
+            let invalid = || usage(format!("invalid value '{}' for --class-weights", value));
+            let (negative, positive) = value.split_once(':').ok_or_else(invalid)?;
+            let negative = negative.parse().map_err(|_| invalid())?;
+            let positive = positive.parse().map_err(|_| invalid())?;
+            ClassWeights::new(negative, positive).map_err(usage)
+        }
+    }
+}
+
+fn train(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(
+        args,
+        &[
+            "task",
+            "arch",
+            "init",
+            "bias",
+            "model",
+            "out",
+            "epochs",
+            "lr",
+            "schedule",
+            "batch",
+            "optimizer",
+            "loss",
+            "class-weights",
+            "seed",
+            "split",
+            "threads",
+            "l1",
+            "l2",
+            "weight-decay",
+            "clip-value",
+            "clip-norm",
+            "format",
+        ],
+    )?;
+    let format = args.format()?;
+    let task = args.task()?;
+
+    let mut model = model_from_args(&args, task)?;
+    if args.value("init").is_some() || args.value("bias").is_some() {
+        if args.value("model").is_some() {
+            return Err(usage(
+                "--init and --bias only apply to new models, not --model".to_string(),
+            ));
+        }
+        let init = args.named("init", Initializer::Uniform, Initializer::from_name)?;
+        model.initialize_weights(init, args.number("bias", 0.0)?);
+    }
+    check_task(&model, task)?;
+    let split_config = args.split(model.seed())?;
+
+    let data = generate_dataset(task);
+    let split = split_dataset(&data, &split_config);
+    let train = select(&data, &split.train);
+    let validation = select(&data, &split.validation);
+    if train.is_empty() {
+        return Err(usage("the training split is empty".to_string()));
+    }
+    let labels: Vec<u8> = train.iter().map(|(_, y)| *y).collect();
+
+    let defaults = TrainConfig::default();
+    let optimizer = args.named("optimizer", defaults.optimizer, OptimizerKind::from_name)?;
+    let config = TrainConfig {
+        epochs: args.number("epochs", defaults.epochs)?,
+        learning_rate: args.number("lr", optimizer.default_learning_rate())?,
+        schedule: args.named("schedule", defaults.schedule, LrSchedule::from_name)?,
+        optimizer,
+        loss: parse_loss(&args, task, &labels)?,
+        batch_size: args.number("batch", defaults.batch_size)?,
+        threads: args.number("threads", defaults.threads)?,
+        regularization: parse_regularization(&args)?,
+        seed: model.seed(),
+        verbose: format == Format::Text,
+    };
+    if config.epochs == 0 || config.batch_size == 0 || config.threads == 0 {
+        return Err(usage(
+            "--epochs, --batch and --threads must be at least 1".to_string(),
+        ));
+    }
+    check_batch_size(&model, config.batch_size).map_err(usage)?;
+
+    config
+        .loss
+        .check_output(model.layers().last())
+        .map_err(usage)?;
+
+    let history = train_epochs(&mut model, &train, &validation, &config);
+    append_logit_sigmoid(&mut model, config.loss);
+    let out = args.value("out").unwrap_or("model.txt");
+    save_model(&model, Path::new(out))
+        .map_err(|e| CliError::Failure(format!("could not save {}: {}", out, e)))?;
+
+    let loss = history.epoch_losses.last().copied().unwrap_or(0.0);
+    let validation_loss = history.validation_losses.last().copied();
+    match format {
+        Format::Text => {
+            println!("Final avg loss: {:.6}", loss);
+            if let Some(v) = validation_loss {
+                println!("Final validation loss: {:.6}", v);
+            }
+            println!("Saved {} to {}", model_spec(&model), out);
+        }
+        Format::Json => println!(
+            "{}",
+            json_object(&[
+                ("task", json_string(&task.name())),
+                ("arch", json_string(&model_spec(&model))),
+                ("optimizer", json_string(config.optimizer.name())),
+                ("loss", json_string(config.loss.name())),
+                ("epochs", config.epochs.to_string()),
+                ("threads", config.threads.to_string()),
+                ("l1", json_number(config.regularization.l1)),
+                ("l2", json_number(config.regularization.l2)),
+                (
+                    "weight_decay",
+                    json_number(config.regularization.weight_decay)
+                ),
+                ("seed", config.seed.to_string()),
+                ("learning_rate", json_number(config.learning_rate)),
+                ("schedule", json_string(&config.schedule.to_string())),
+                (
+                    "final_learning_rate",
+                    json_number(history.learning_rates.last().copied().unwrap_or(0.0))
+                ),
+                ("final_loss", json_number(loss)),
+                (
+                    "validation_loss",
+                    validation_loss.map_or("null".to_string(), json_number)
+                ),
+                ("model", json_string(out)),
+            ])
+        ),
+    }
+    Ok(())
+}
+
+fn parse_regularization(args: &Args) -> Result<Regularization, CliError> {
+    let optional = |name: &str| -> Result<Option<f32>, CliError> {
+        match args.value(name) {
+            Some(_) => match args.number(name, 0.0)? {
+                limit if limit > 0.0 => Ok(Some(limit)),
+                _ => Err(usage(format!("--{} must be positive", name))),
+            },
+            None => Ok(None),
+        }
+    };
+    let regularization = Regularization {
+        l1: args.number("l1", 0.0)?,
+        l2: args.number("l2", 0.0)?,
+        weight_decay: args.number("weight-decay", 0.0)?,
+        clip_value: optional("clip-value")?,
+        clip_norm: optional("clip-norm")?,
+    };
+    if regularization.l1 < 0.0 || regularization.l2 < 0.0 || regularization.weight_decay < 0.0 {
+        return Err(usage(
+            "--l1, --l2 and --weight-decay must not be negative".to_string(),
+        ));
+    }
+    Ok(regularization)
+}
+
+fn predict(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &["model", "format"])?;
+    let format = args.format()?;
+    let model = read_model(args.model_path()?)?;
+    check_input_width(&model)?;
+
+    let mut values = args.positional.clone();
+    if values.is_empty() {
+        let mut input = String::new();
+        io::stdin()
+            .read_to_string(&mut input)
+            .map_err(|e| CliError::Failure(format!("could not read stdin: {}", e)))?;
+        values = input.split_whitespace().map(str::to_string).collect();
+    }
+    let numbers = values
+        .iter()
+        .map(|v| match v.parse::<u16>() {
+            Ok(n) if n <= 1023 => Ok(n),
+            _ => Err(usage(format!("'{}' is not a number from 0 to 1023", v))),
+        })
+        .collect::<Result<Vec<u16>, CliError>>()?;
+
+    let mut rows = Vec::new();
+    for n in numbers {
+        let bits = u16_to_bin_vector(n);
+        let input = Matrix::from_vec(vec![bits.iter().map(|&b| b as f32).collect()]);
+        let output = model.predict(&input);
+        let scores = output.row(0).to_vec();
+        let class = if scores.len() == 1 {
+            (scores[0] >= model.threshold()) as usize
+        } else {
+            argmax(&scores)
+        };
+        match format {
+            Format::Text => {
+                let scores: Vec<String> = scores.iter().map(|s| format!("{:.4}", s)).collect();
+                println!("{} {} {}", n, class, scores.join(" "));
+            }
+            Format::Json => {
+                let scores: Vec<String> = scores.iter().map(|&s| json_number(s)).collect();
+                rows.push(json_object(&[
+                    ("number", n.to_string()),
+                    ("class", class.to_string()),
+                    ("scores", format!("[{}]", scores.join(","))),
+                ]));
+            }
+        }
+    }
+    if format == Format::Json {
+        println!("[{}]", rows.join(","));
+    }
+    Ok(())
+}
+
+fn evaluate(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &["model", "task", "set", "split", "seed", "format"])?;
+    let format = args.format()?;
+    let task = args.task()?;
+    let model = read_model(args.model_path()?)?;
+    check_task(&model, task)?;
+    let split_config = args.split(args.number("seed", model.seed())?)?;
+
+    let data = generate_dataset(task);
+    let split = split_dataset(&data, &split_config);
+    let set = args.value("set").unwrap_or("test");
+    let indices = match set {
+        "train" => split.train,
+        "validation" => split.validation,
+        "test" => split.test,
+        "all" => (0..data.len()).collect(),
+        _ => return Err(usage(format!("unknown set '{}'", set))),
+    };
+    if indices.is_empty() {
+        return Err(usage(format!("the {} set is empty", set)));
+    }
+
+    let mut fields = vec![
+        ("task", json_string(&task.name())),
+        ("set", json_string(set)),
+        ("samples", indices.len().to_string()),
+    ];
+    if task.num_classes() == 1 {
+        let (scores, labels) = binary_scores(&model, &data, &indices);
+        let m = BinaryMetrics::from_scores(&scores, &labels, model.threshold());
+        fields.extend([
+            ("threshold", json_number(m.threshold)),
+            ("accuracy", json_number(m.accuracy())),
+            ("precision", json_number(m.precision())),
+            ("recall", json_number(m.recall())),
+            ("specificity", json_number(m.specificity())),
+            ("f1", json_number(m.f1())),
+            ("balanced_accuracy", json_number(m.balanced_accuracy())),
+            ("roc_auc", json_number(roc_auc(&scores, &labels))),
+            ("pr_auc", json_number(pr_auc(&scores, &labels))),
+        ]);
+    } else {
+        let (correct, _) = class_counts(&model, &data, &indices, task.num_classes());
+        let accuracy = correct.iter().sum::<usize>() as f32 / indices.len() as f32;
+        fields.push(("accuracy", json_number(accuracy)));
+    }
+    print_fields(&fields, format);
+    Ok(())
+}
+
+fn inspect(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &["model", "format"])?;
+    let format = args.format()?;
+    let model = read_model(args.model_path()?)?;
+    let parameters: usize = model
+        .layers()
+        .iter()
+        .map(|layer| match layer {
+            LayerEnum::Dense(d) => d.weights.rows() * d.weights.cols() + d.biases.cols(),
+            LayerEnum::BatchNorm(b) => 2 * b.size(),
+            LayerEnum::LayerNorm(l) => 2 * l.size(),
+            _ => 0,
+        })
+        .sum();
+    print_fields(
+        &[
+            ("arch", json_string(&model_spec(&model))),
+            ("inputs", model.input_size().to_string()),
+            ("outputs", model.output_size().to_string()),
+            ("layers", model.layers().len().to_string()),
+            ("parameters", parameters.to_string()),
+            ("threshold", json_number(model.threshold())),
+            ("seed", model.seed().to_string()),
+        ],
+        format,
+    );
+    Ok(())
+}
+
+fn gradcheck(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(
+        args,
+        &[
+            "task",
+            "arch",
+            "model",
+            "loss",
+            "class-weights",
+            "batch",
+            "step",
+            "mode",
+            "seed",
+            "split",
+            "format",
+        ],
+    )?;
+    let format = args.format()?;
+    let task = args.task()?;
+    let mut model = model_from_args(&args, task)?;
+    check_task(&model, task)?;
+    let step = args.number("step", DEFAULT_STEP)?;
+    if step <= 0.0 {
+        return Err(usage("--step must be positive".to_string()));
+    }
+    match args.value("mode").unwrap_or("train") {
+        "train" => model.train(),
+        "eval" => model.eval(),
+        name => return Err(usage(format!("unknown mode '{}'", name))),
+    }
+
+    let data = generate_dataset(task);
+    let split = split_dataset(&data, &args.split(model.seed())?);
+    let batch = args.number("batch", 8)?.min(split.train.len());
+    if batch == 0 {
+        return Err(usage("--batch must be at least 1".to_string()));
+    }
+    let samples = select(&data, &split.train[..batch]);
+    let labels: Vec<u8> = samples.iter().map(|(_, y)| *y).collect();
+    let loss = parse_loss(&args, task, &labels)?;
+    loss.check_output(model.layers().last()).map_err(usage)?;
+    let (input, target) = batch_to_matrices(&samples, model.output_size());
+
+    let report = check_gradients(&model, &input, &target, loss, step);
+    match format {
+        Format::Text => report.print(),
+        Format::Json => {
+            let errors: Vec<String> = report
+                .layer_errors
+                .iter()
+                .map(|&e| json_number(e))
+                .collect();
+            println!(
+                "{}",
+                json_object(&[
+                    ("arch", json_string(&model_spec(&model))),
+                    ("loss", json_number(report.loss)),
+                    ("layer_errors", format!("[{}]", errors.join(","))),
+                    ("max_error", json_number(report.max_error())),
+                ])
+            );
+        }
+    }
+    Ok(())
+}
+
+fn bench(args: &[String]) -> Result<(), CliError> {
+    let args = Args::parse(args, &["arch", "repeats", "seed", "format"])?;
+    let format = args.format()?;
+    let arch = args.value("arch").unwrap_or(DEFAULT_BENCH_SPEC);
+    let model = parse_spec(arch, args.number("seed", DEFAULT_SEED)?)
+        .map_err(|e| usage(format!("invalid --arch: {}", e)))?;
+    let repeats = args.number("repeats", 20)?;
+    if repeats == 0 {
+        return Err(usage("--repeats must be at least 1".to_string()));
+    }
+
+    let results = run_benchmarks(&model, repeats);
+    let baseline = results[0].per_pass.as_secs_f64();
+    match format {
+        Format::Text => {
+            println!("{} over 1024 samples, {} passes each:", arch, repeats);
+            for result in &results {
+                println!(
+                    "  {:<28} {:>10.3} ms/pass  {:>7.1}x",
+                    result.name,
+                    result.per_pass.as_secs_f64() * 1000.0,
+                    baseline / result.per_pass.as_secs_f64()
+                );
+            }
+        }
+        Format::Json => {
+            let rows: Vec<String> = results
+                .iter()
+                .map(|result| {
+                    json_object(&[
+                        ("name", json_string(result.name)),
+                        (
+                            "ms_per_pass",
+                            json_number(result.per_pass.as_secs_f64() * 1000.0),
+                        ),
+                        (
+                            "speedup",
+                            json_number(baseline / result.per_pass.as_secs_f64()),
+                        ),
+                    ])
+                })
+                .collect();
+            println!("[{}]", rows.join(","));
+        }
+    }
+    Ok(())
+}
+
+/// Prints `key: value` lines, or one JSON object. Values are JSON-encoded;
+/// text output strips the quotes from strings.
+fn print_fields(fields: &[(&str, String)], format: Format) {
+    match format {
+        Format::Text => {
+            for (key, value) in fields {
+                println!("{}: {}", key, value.trim_matches('"'));
+            }
+        }
+        Format::Json => println!("{}", json_object(fields)),
+    }
+}
+
+fn json_object(fields: &[(&str, String)]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(key, value)| format!("{}:{}", json_string(key), value))
+        .collect();
+    format!("{{{}}}", fields.join(","))
+}
+
+fn json_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// JSON has no NaN or infinity, so those become `null`. Takes `f32`
+/// scores and `f64` timings alike.
+fn json_number<T: Copy + Into<f64> + ToString>(x: T) -> String {
+    if x.into().is_finite() {
+        x.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = Args::parse(
+            &strings(&["--epochs", "5", "--lr=0.2", "7", "9"]),
+            &["epochs", "lr"],
+        )
+        .ok()
+        .unwrap();
+        assert_eq!(args.number("epochs", 1).ok(), Some(5));
+        assert_eq!(args.number("lr", 0.1).ok(), Some(0.2));
+        assert_eq!(args.number("batch", 3).ok(), Some(3));
+        assert_eq!(args.positional, strings(&["7", "9"]));
+        assert!(Args::parse(&strings(&["--nope", "1"]), &["epochs"]).is_err());
+        assert!(Args::parse(&strings(&["--epochs"]), &["epochs"]).is_err());
+        assert!(matches!(
+            Args::parse(&strings(&["--epochs", "5", "--help"]), &["epochs"]),
+            Err(CliError::Help)
+        ));
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(run(&strings(&["help"])), 0);
+        assert_eq!(run(&strings(&["train", "--help"])), 0);
+        assert_eq!(run(&strings(&["evaluate", "--task", "primality", "-h"])), 0);
+        assert_eq!(run(&strings(&["frobnicate"])), EXIT_USAGE);
+        assert_eq!(run(&strings(&["train", "--epochs", "many"])), EXIT_USAGE);
+        assert_eq!(run(&strings(&["inspect"])), EXIT_USAGE);
+        assert_eq!(
+            run(&strings(&["inspect", "--model", "/nonexistent/model.txt"])),
+            EXIT_FAILURE
+        );
+
+        // A model of the wrong input width is a usage error for every
+        // command that reads numbers.
+        let path = std::env::temp_dir().join(format!("cli-narrow-{}.txt", std::process::id()));
+        save_model(&parse_spec("4-d1-sigmoid", 1).unwrap(), &path).unwrap();
+        let model = path.to_str().unwrap();
+        assert_eq!(
+            run(&strings(&["predict", "--model", model, "5"])),
+            EXIT_USAGE
+        );
+        assert_eq!(run(&strings(&["evaluate", "--model", model])), EXIT_USAGE);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_train_then_evaluate() {
+        let path = std::env::temp_dir().join(format!("cli-test-{}.txt", std::process::id()));
+        let out = path.to_str().unwrap();
+        let train = [
+            "train",
+            "--arch",
+            "10-d4-relu-d1-sigmoid",
+            "--epochs",
+            "1",
+            "--format",
+            "json",
+            "--out",
+            out,
+        ];
+        assert_eq!(run(&strings(&train)), 0);
+        assert_eq!(run(&strings(&["evaluate", "--model", out])), 0);
+        assert_eq!(run(&strings(&["predict", "--model", out, "7", "8"])), 0);
+        assert_eq!(
+            run(&strings(&["gradcheck", "--model", out, "--batch", "2"])),
+            0
+        );
+        assert_eq!(
+            run(&strings(&["predict", "--model", out, "2048"])),
+            EXIT_USAGE
+        );
+        assert_eq!(
+            run(&strings(&["evaluate", "--model", out, "--task", "mod:3"])),
+            EXIT_USAGE
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_json_encoding() {
+        assert_eq!(
+            json_object(&[("a", json_string("x\"y")), ("b", json_number(f32::NAN))]),
+            r#"{"a":"x\"y","b":null}"#
+        );
+        assert_eq!(json_number(f64::INFINITY), "null");
+        assert_eq!(json_number(0.1f32), "0.1");
+        assert_eq!(json_number(2.5f64), "2.5");
+    }
+}
@@ -1,5 +1,16 @@
+use crate::model::DEFAULT_SEED;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+/// Width of a model input: every number below 1024 as 10 bits.
+pub const INPUT_BITS: usize = 10;
+
 pub fn u16_to_bin_vector(n: u16) -> Vec<u8> {
-    (0..10).rev().map(|i| ((n >> i) & 1) as u8).collect()
+    (0..INPUT_BITS)
+        .rev()
+        .map(|i| ((n >> i) & 1) as u8)
+        .collect()
 }
 
 pub fn is_prime(n: u16) -> bool {
@@ -7,23 +18,226 @@ pub fn is_prime(n: u16) -> bool {
         return false;
     }
     for i in 2..=((n as f64).sqrt() as u16) {
-        if n % i == 0 {
+        if n.is_multiple_of(i) {
             return false;
         }
     }
     true
 }
 
-pub fn generate_dataset() -> Vec<(Vec<u8>, u8)> {
+/// Primes that can be the smallest factor of a composite number below 1024.
+const SMALL_PRIMES: [u16; 11] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31];
+
+/// What the model is asked to predict about each number.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Task {
+    /// 1 if the number is prime, else 0, predicted by a single output.
+    Primality,
+    /// `n mod k`, one class per remainder.
+    Modulo(u16),
+    /// Number of prime factors counted with multiplicity (0 to 9).
+    PrimeFactorCount,
+    /// Class 0 for 0 and 1, classes 1-11 for smallest factors 2 to 31, and
+    /// class 12 for primes above 31.
+    SmallestPrimeFactor,
+}
+
+impl Task {
+    pub fn name(&self) -> String {
+        match self {
+            Task::Primality => "primality".to_string(),
+            Task::Modulo(k) => format!("mod {}", k),
+            Task::PrimeFactorCount => "prime factor count".to_string(),
+            Task::SmallestPrimeFactor => "smallest prime factor".to_string(),
+        }
+    }
+
+    /// Parses the command-line form: `primality`, `mod:K` with K from 2 to
+    /// 64, `prime_factor_count` or `smallest_prime_factor`.
+    pub fn from_name(name: &str) -> Option<Task> {
+        match name {
+            "primality" => Some(Task::Primality),
+            "prime_factor_count" => Some(Task::PrimeFactorCount),
+            "smallest_prime_factor" => Some(Task::SmallestPrimeFactor),
+            _ => {
+                let k: u16 = name.strip_prefix("mod:")?.parse().ok()?;
+                (2..=64).contains(&k).then_some(Task::Modulo(k))
+            }
+        }
+    }
+
+    /// Width of the model output. Primality uses one sigmoid output, the
+    /// other tasks one softmax output per class.
+    pub fn num_classes(&self) -> usize {
+        match self {
+            Task::Primality => 1,
+            Task::Modulo(k) => *k as usize,
+            Task::PrimeFactorCount => 10,
+            Task::SmallestPrimeFactor => SMALL_PRIMES.len() + 2,
+        }
+    }
+
+    pub fn label(&self, n: u16) -> u8 {
+        match self {
+            Task::Primality => is_prime(n) as u8,
+            Task::Modulo(k) => (n % k) as u8,
+            Task::PrimeFactorCount => prime_factor_count(n) as u8,
+            Task::SmallestPrimeFactor => {
+                if n < 2 {
+                    return 0;
+                }
+                match SMALL_PRIMES.iter().position(|&p| n.is_multiple_of(p)) {
+                    Some(i) => i as u8 + 1,
+                    None => SMALL_PRIMES.len() as u8 + 1,
+                }
+            }
+        }
+    }
+
+    /// Human-readable meaning of a class index.
+    pub fn class_name(&self, class: usize) -> String {
+        match self {
+            Task::Primality => if class == 1 { "prime" } else { "not prime" }.to_string(),
+            Task::Modulo(k) => format!("≡ {} (mod {})", class, k),
+            Task::PrimeFactorCount => format!("{} prime factor(s)", class),
+            Task::SmallestPrimeFactor => match class {
+                0 => "no prime factor".to_string(),
+                c if c <= SMALL_PRIMES.len() => format!("smallest factor {}", SMALL_PRIMES[c - 1]),
+                _ => "prime above 31".to_string(),
+            },
+        }
+    }
+}
+
+pub fn prime_factor_count(mut n: u16) -> usize {
+    let mut count = 0;
+    let mut p = 2;
+    while n >= 2 && p * p <= n {
+        while n.is_multiple_of(p) {
+            n /= p;
+            count += 1;
+        }
+        p += 1;
+    }
+    if n >= 2 {
+        count += 1;
+    }
+    count
+}
+
+pub fn generate_dataset(task: Task) -> Vec<(Vec<u8>, u8)> {
     (0..=1023)
-        .map(|n| {
-            let input = u16_to_bin_vector(n);
-            let label = if is_prime(n) { 1 } else { 0 };
-            (input, label)
-        })
+        .map(|n| (u16_to_bin_vector(n), task.label(n)))
         .collect()
 }
 
+/// How `split_dataset` assigns samples to the train, validation and test sets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SplitStrategy {
+    /// Shuffle all samples, then cut.
+    Random,
+    /// Shuffle and cut each label separately so every set keeps the label
+    /// proportions of the whole dataset.
+    Stratified,
+    /// Cut in numeric order, e.g. train on 0-767 and test on 768-1023.
+    Range,
+}
+
+impl SplitStrategy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SplitStrategy::Random => "random",
+            SplitStrategy::Stratified => "stratified",
+            SplitStrategy::Range => "range",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<SplitStrategy> {
+        match name {
+            "random" => Some(SplitStrategy::Random),
+            "stratified" => Some(SplitStrategy::Stratified),
+            "range" => Some(SplitStrategy::Range),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SplitConfig {
+    pub strategy: SplitStrategy,
+    pub train_fraction: f32,
+    pub validation_fraction: f32,
+    /// Seed for the shuffle; ignored by `SplitStrategy::Range`.
+    pub seed: u64,
+}
+
+impl Default for SplitConfig {
+    fn default() -> Self {
+        SplitConfig {
+            strategy: SplitStrategy::Stratified,
+            train_fraction: 0.7,
+            validation_fraction: 0.15,
+            seed: DEFAULT_SEED,
+        }
+    }
+}
+
+/// Sample indices of each set, in ascending order. The index of a sample in
+/// `generate_dataset` is also the number it encodes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DatasetSplit {
+    pub train: Vec<usize>,
+    pub validation: Vec<usize>,
+    pub test: Vec<usize>,
+}
+
+/// Splits `data` into train, validation and test sets. The test set gets
+/// whatever the train and validation fractions leave over.
+pub fn split_dataset(data: &[(Vec<u8>, u8)], config: &SplitConfig) -> DatasetSplit {
+    let mut split = DatasetSplit::default();
+    let mut rng = StdRng::seed_from_u64(config.seed);
+
+    let groups: Vec<Vec<usize>> = match config.strategy {
+        SplitStrategy::Random | SplitStrategy::Range => vec![(0..data.len()).collect()],
+        SplitStrategy::Stratified => {
+            let mut labels: Vec<u8> = data.iter().map(|(_, y)| *y).collect();
+            labels.sort_unstable();
+            labels.dedup();
+            labels
+                .iter()
+                .map(|&label| (0..data.len()).filter(|&i| data[i].1 == label).collect())
+                .collect()
+        }
+    };
+
+    for mut group in groups {
+        if config.strategy != SplitStrategy::Range {
+            group.shuffle(&mut rng);
+        }
+        let n = group.len() as f32;
+        let train_end = ((n * config.train_fraction).round() as usize).min(group.len());
+        let validation_end = ((n * (config.train_fraction + config.validation_fraction)).round()
+            as usize)
+            .clamp(train_end, group.len());
+
+        split.train.extend_from_slice(&group[..train_end]);
+        split
+            .validation
+            .extend_from_slice(&group[train_end..validation_end]);
+        split.test.extend_from_slice(&group[validation_end..]);
+    }
+
+    split.train.sort_unstable();
+    split.validation.sort_unstable();
+    split.test.sort_unstable();
+    split
+}
+
+/// Copies the samples at `indices` out of `data`.
+pub fn select(data: &[(Vec<u8>, u8)], indices: &[usize]) -> Vec<(Vec<u8>, u8)> {
+    indices.iter().map(|&i| data[i].clone()).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -51,7 +265,109 @@ mod tests {
 
     #[test]
     fn test_generate_dataset_size() {
-        let data = generate_dataset();
+        let data = generate_dataset(Task::Primality);
         assert_eq!(data.len(), 1024);
     }
+
+    #[test]
+    fn test_prime_factor_count() {
+        assert_eq!(prime_factor_count(0), 0);
+        assert_eq!(prime_factor_count(1), 0);
+        assert_eq!(prime_factor_count(7), 1);
+        assert_eq!(prime_factor_count(12), 3);
+        assert_eq!(prime_factor_count(512), 9);
+        assert_eq!(prime_factor_count(1021), 1);
+    }
+
+    #[test]
+    fn test_task_labels_fit_class_count() {
+        let tasks = [
+            Task::Primality,
+            Task::Modulo(3),
+            Task::PrimeFactorCount,
+            Task::SmallestPrimeFactor,
+        ];
+        for task in tasks {
+            let classes = task.num_classes().max(2);
+            let data = generate_dataset(task);
+            assert!(data.iter().all(|(_, y)| (*y as usize) < classes));
+        }
+        assert_eq!(Task::Modulo(3).label(10), 1);
+        assert_eq!(Task::SmallestPrimeFactor.label(1), 0);
+        assert_eq!(Task::SmallestPrimeFactor.label(15), 2);
+        assert_eq!(Task::SmallestPrimeFactor.label(961), 11);
+        assert_eq!(Task::SmallestPrimeFactor.label(37), 12);
+    }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(Task::from_name("primality"), Some(Task::Primality));
+        assert_eq!(Task::from_name("mod:7"), Some(Task::Modulo(7)));
+        assert_eq!(Task::from_name("mod:1"), None);
+        assert_eq!(Task::from_name("mod"), None);
+        assert_eq!(
+            Task::from_name("smallest_prime_factor"),
+            Some(Task::SmallestPrimeFactor)
+        );
+        for strategy in [
+            SplitStrategy::Random,
+            SplitStrategy::Stratified,
+            SplitStrategy::Range,
+        ] {
+            assert_eq!(SplitStrategy::from_name(strategy.name()), Some(strategy));
+        }
+    }
+
+    #[test]
+    fn test_split_partitions_dataset() {
+        let data = generate_dataset(Task::Primality);
+        for strategy in [
+            SplitStrategy::Random,
+            SplitStrategy::Stratified,
+            SplitStrategy::Range,
+        ] {
+            let config = SplitConfig {
+                strategy,
+                ..SplitConfig::default()
+            };
+            let split = split_dataset(&data, &config);
+            let mut all: Vec<usize> = split
+                .train
+                .iter()
+                .chain(&split.validation)
+                .chain(&split.test)
+                .cloned()
+                .collect();
+            all.sort_unstable();
+            assert_eq!(all, (0..1024).collect::<Vec<_>>());
+            assert!((split.train.len() as i32 - 717).abs() <= 2);
+            assert_eq!(split, split_dataset(&data, &config));
+        }
+    }
+
+    #[test]
+    fn test_range_split_keeps_numeric_order() {
+        let data = generate_dataset(Task::Primality);
+        let config = SplitConfig {
+            strategy: SplitStrategy::Range,
+            train_fraction: 0.75,
+            validation_fraction: 0.0,
+            seed: 0,
+        };
+        let split = split_dataset(&data, &config);
+        assert_eq!(split.train, (0..768).collect::<Vec<_>>());
+        assert!(split.validation.is_empty());
+        assert_eq!(split.test, (768..1024).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_stratified_split_keeps_label_ratio() {
+        let data = generate_dataset(Task::Primality);
+        let split = split_dataset(&data, &SplitConfig::default());
+        let primes = |indices: &[usize]| indices.iter().filter(|&&i| data[i].1 == 1).count();
+        // 172 primes below 1024: 70% / 15% / 15%.
+        assert_eq!(primes(&split.train), 120);
+        assert_eq!(primes(&split.validation), 26);
+        assert_eq!(primes(&split.test), 26);
+    }
 }
@@ -0,0 +1,405 @@
+use crate::dataset::select;
+use crate::matrix::Matrix;
+use crate::model::Model;
+use crate::parallel::{available_threads, predict_parallel};
+use crate::train::{argmax, batch_to_matrices};
+
+/// Confusion-matrix counts and the metrics derived from them for a binary
+/// classifier whose scores are cut at `threshold`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BinaryMetrics {
+    pub threshold: f32,
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub true_negatives: usize,
+    pub false_negatives: usize,
+}
+
+/// `numerator / denominator`, or 0 when nothing was counted.
+fn ratio(numerator: usize, denominator: usize) -> f32 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f32 / denominator as f32
+    }
+}
+
+impl BinaryMetrics {
+    pub fn from_scores(scores: &[f32], labels: &[u8], threshold: f32) -> Self {
+        let mut metrics = BinaryMetrics {
+            threshold,
+            true_positives: 0,
+            false_positives: 0,
+            true_negatives: 0,
+            false_negatives: 0,
+        };
+        for (&score, &label) in scores.iter().zip(labels) {
+            match (score >= threshold, label == 1) {
+                (true, true) => metrics.true_positives += 1,
+                (true, false) => metrics.false_positives += 1,
+                (false, false) => metrics.true_negatives += 1,
+                (false, true) => metrics.false_negatives += 1,
+            }
+        }
+        metrics
+    }
+
+    pub fn total(&self) -> usize {
+        self.true_positives + self.false_positives + self.true_negatives + self.false_negatives
+    }
+
+    pub fn accuracy(&self) -> f32 {
+        ratio(self.true_positives + self.true_negatives, self.total())
+    }
+
+    pub fn precision(&self) -> f32 {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_positives,
+        )
+    }
+
+    /// Also called sensitivity or true positive rate.
+    pub fn recall(&self) -> f32 {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_negatives,
+        )
+    }
+
+    /// True negative rate.
+    pub fn specificity(&self) -> f32 {
+        ratio(
+            self.true_negatives,
+            self.true_negatives + self.false_positives,
+        )
+    }
+
+    pub fn f1(&self) -> f32 {
+        let (p, r) = (self.precision(), self.recall());
+        if p + r == 0.0 {
+            0.0
+        } else {
+            2.0 * p * r / (p + r)
+        }
+    }
+
+    pub fn balanced_accuracy(&self) -> f32 {
+        (self.recall() + self.specificity()) / 2.0
+    }
+
+    pub fn print(&self) {
+        println!("🧮 Confusion matrix (threshold {:.2}):", self.threshold);
+        println!("                 predicted 1  predicted 0");
+        println!(
+            "  actual 1       {:>11}  {:>11}",
+            self.true_positives, self.false_negatives
+        );
+        println!(
+            "  actual 0       {:>11}  {:>11}",
+            self.false_positives, self.true_negatives
+        );
+        println!("✅ Accuracy:          {:.4}", self.accuracy());
+        println!("🎯 Precision:         {:.4}", self.precision());
+        println!("🔍 Recall:            {:.4}", self.recall());
+        println!("🧩 F1:                {:.4}", self.f1());
+        println!("🛡️ Specificity:       {:.4}", self.specificity());
+        println!("⚖️ Balanced accuracy: {:.4}", self.balanced_accuracy());
+    }
+}
+
+/// What `best_threshold` maximizes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThresholdCriterion {
+    F1,
+    BalancedAccuracy,
+}
+
+impl ThresholdCriterion {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ThresholdCriterion::F1 => "F1",
+            ThresholdCriterion::BalancedAccuracy => "balanced accuracy",
+        }
+    }
+
+    pub fn score(&self, metrics: &BinaryMetrics) -> f32 {
+        match self {
+            ThresholdCriterion::F1 => metrics.f1(),
+            ThresholdCriterion::BalancedAccuracy => metrics.balanced_accuracy(),
+        }
+    }
+}
+
+/// Metrics at `steps + 1` evenly spaced thresholds from 0 to 1.
+pub fn threshold_sweep(scores: &[f32], labels: &[u8], steps: usize) -> Vec<BinaryMetrics> {
+    (0..=steps)
+        .map(|i| BinaryMetrics::from_scores(scores, labels, i as f32 / steps as f32))
+        .collect()
+}
+
+/// The swept threshold with the highest `criterion`, preferring the lowest
+/// threshold on ties.
+pub fn best_threshold(sweep: &[BinaryMetrics], criterion: ThresholdCriterion) -> BinaryMetrics {
+    sweep
+        .iter()
+        .copied()
+        .reduce(|best, m| {
+            if criterion.score(&m) > criterion.score(&best) {
+                m
+            } else {
+                best
+            }
+        })
+        .expect("threshold sweep is empty")
+}
+
+/// Area under the ROC curve: the probability that a random positive scores
+/// higher than a random negative, with ties counting half. Returns 0.5 when
+/// one of the classes is missing.
+pub fn roc_auc(scores: &[f32], labels: &[u8]) -> f32 {
+    let mut order: Vec<usize> = (0..scores.len()).collect();
+    order.sort_by(|&a, &b| scores[a].total_cmp(&scores[b]));
+
+    // Sum the 1-based ranks of the positives, averaging ranks across ties.
+    let mut positive_rank_sum = 0.0f64;
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start;
+        while end + 1 < order.len() && scores[order[end + 1]] == scores[order[start]] {
+            end += 1;
+        }
+        let average_rank = (start + end) as f64 / 2.0 + 1.0;
+        for &i in &order[start..=end] {
+            if labels[i] == 1 {
+                positive_rank_sum += average_rank;
+            }
+        }
+        start = end + 1;
+    }
+
+    let positives = labels.iter().filter(|&&y| y == 1).count() as f64;
+    let negatives = labels.len() as f64 - positives;
+    if positives == 0.0 || negatives == 0.0 {
+        return 0.5;
+    }
+    ((positive_rank_sum - positives * (positives + 1.0) / 2.0) / (positives * negatives)) as f32
+}
+
+/// Area under the precision-recall curve, computed as average precision:
+/// the precision at each distinct score, weighted by the recall gained there.
+pub fn pr_auc(scores: &[f32], labels: &[u8]) -> f32 {
+    let positives = labels.iter().filter(|&&y| y == 1).count();
+    if positives == 0 {
+        return 0.0;
+    }
+
+    let mut order: Vec<usize> = (0..scores.len()).collect();
+    order.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+
+    let mut area = 0.0;
+    let mut true_positives = 0;
+    let mut seen = 0;
+    let mut previous_recall = 0.0;
+    let mut i = 0;
+    while i < order.len() {
+        // Samples with equal scores cross the threshold together.
+        let score = scores[order[i]];
+        while i < order.len() && scores[order[i]] == score {
+            true_positives += (labels[order[i]] == 1) as usize;
+            seen += 1;
+            i += 1;
+        }
+        let recall = ratio(true_positives, positives);
+        area += (recall - previous_recall) * ratio(true_positives, seen);
+        previous_recall = recall;
+    }
+    area
+}
+
+/// One row of a reliability table: the samples whose score fell in
+/// `[lower, upper)`, how confident the model was on them on average, and
+/// how often they were actually positive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CalibrationBin {
+    pub lower: f32,
+    pub upper: f32,
+    pub count: usize,
+    pub mean_score: f32,
+    pub positive_rate: f32,
+}
+
+/// Groups the scores into `bins` equal-width bins over [0, 1]. A
+/// well-calibrated model has `mean_score` close to `positive_rate` in every
+/// bin.
+pub fn calibration_table(scores: &[f32], labels: &[u8], bins: usize) -> Vec<CalibrationBin> {
+    let mut counts = vec![0; bins];
+    let mut score_sums = vec![0.0; bins];
+    let mut positives = vec![0; bins];
+    for (&score, &label) in scores.iter().zip(labels) {
+        let bin = ((score.clamp(0.0, 1.0) * bins as f32) as usize).min(bins - 1);
+        counts[bin] += 1;
+        score_sums[bin] += score;
+        positives[bin] += (label == 1) as usize;
+    }
+
+    (0..bins)
+        .map(|b| CalibrationBin {
+            lower: b as f32 / bins as f32,
+            upper: (b + 1) as f32 / bins as f32,
+            count: counts[b],
+            mean_score: if counts[b] == 0 {
+                0.0
+            } else {
+                score_sums[b] / counts[b] as f32
+            },
+            positive_rate: ratio(positives[b], counts[b]),
+        })
+        .collect()
+}
+
+/// Expected calibration error: the count-weighted mean gap between
+/// confidence and observed positive rate.
+pub fn expected_calibration_error(table: &[CalibrationBin]) -> f32 {
+    let total: usize = table.iter().map(|b| b.count).sum();
+    table
+        .iter()
+        .map(|b| b.count as f32 * (b.mean_score - b.positive_rate).abs())
+        .sum::<f32>()
+        / total.max(1) as f32
+}
+
+pub fn print_calibration_table(table: &[CalibrationBin]) {
+    println!("📏 Calibration:");
+    println!("  score range   count  mean score  positive rate");
+    for bin in table.iter().filter(|b| b.count > 0) {
+        println!(
+            "  {:.1} - {:.1}   {:>6}  {:>10.4}  {:>13.4}",
+            bin.lower, bin.upper, bin.count, bin.mean_score, bin.positive_rate
+        );
+    }
+    println!(
+        "  Expected calibration error: {:.4}",
+        expected_calibration_error(table)
+    );
+}
+
+/// Raw model scores and labels of the samples at `indices`.
+pub fn binary_scores(
+    model: &Model,
+    data: &[(Vec<u8>, u8)],
+    indices: &[usize],
+) -> (Vec<f32>, Vec<u8>) {
+    let (output, labels) = predict_samples(model, data, indices);
+    (output.as_slice().to_vec(), labels)
+}
+
+/// Correct predictions and sample counts per class for a multi-class model,
+/// reading each prediction as the likeliest class.
+pub fn class_counts(
+    model: &Model,
+    data: &[(Vec<u8>, u8)],
+    indices: &[usize],
+    classes: usize,
+) -> (Vec<usize>, Vec<usize>) {
+    let mut correct = vec![0; classes];
+    let mut totals = vec![0; classes];
+    let (output, labels) = predict_samples(model, data, indices);
+    for (r, &y) in labels.iter().enumerate() {
+        totals[y as usize] += 1;
+        if argmax(output.row(r)) == y as usize {
+            correct[y as usize] += 1;
+        }
+    }
+    (correct, totals)
+}
+
+/// Model output for the samples at `indices`, one row each, computed as
+/// one batch split across all cores, with the samples' labels.
+fn predict_samples(model: &Model, data: &[(Vec<u8>, u8)], indices: &[usize]) -> (Matrix, Vec<u8>) {
+    let samples = select(data, indices);
+    let (input, _) = batch_to_matrices(&samples, 1);
+    let output = predict_parallel(model, &input, available_threads());
+    (output, samples.iter().map(|(_, y)| *y).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binary_metrics() {
+        let scores = [0.9, 0.8, 0.3, 0.6, 0.2, 0.1];
+        let labels = [1, 1, 1, 0, 0, 0];
+        let m = BinaryMetrics::from_scores(&scores, &labels, 0.5);
+        assert_eq!(
+            (
+                m.true_positives,
+                m.false_negatives,
+                m.false_positives,
+                m.true_negatives
+            ),
+            (2, 1, 1, 2)
+        );
+        assert!((m.precision() - 2.0 / 3.0).abs() < 1e-6);
+        assert!((m.recall() - 2.0 / 3.0).abs() < 1e-6);
+        assert!((m.f1() - 2.0 / 3.0).abs() < 1e-6);
+        assert!((m.specificity() - 2.0 / 3.0).abs() < 1e-6);
+        assert!((m.balanced_accuracy() - 2.0 / 3.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_metrics_without_positive_predictions() {
+        let m = BinaryMetrics::from_scores(&[0.1, 0.2], &[1, 0], 0.5);
+        assert_eq!(m.precision(), 0.0);
+        assert_eq!(m.f1(), 0.0);
+        assert_eq!(m.accuracy(), 0.5);
+        assert_eq!(m.balanced_accuracy(), 0.5);
+    }
+
+    #[test]
+    fn test_threshold_sweep_finds_separating_threshold() {
+        let scores = [0.1, 0.2, 0.3, 0.35, 0.4, 0.9];
+        let labels = [0, 0, 0, 1, 1, 1];
+        let sweep = threshold_sweep(&scores, &labels, 100);
+        assert_eq!(sweep.len(), 101);
+        assert_eq!(sweep[0].threshold, 0.0);
+        assert_eq!(sweep[100].threshold, 1.0);
+
+        for criterion in [ThresholdCriterion::F1, ThresholdCriterion::BalancedAccuracy] {
+            let best = best_threshold(&sweep, criterion);
+            assert!(best.threshold > 0.3 && best.threshold <= 0.35);
+            assert_eq!(criterion.score(&best), 1.0);
+        }
+    }
+
+    #[test]
+    fn test_roc_auc() {
+        assert_eq!(roc_auc(&[0.1, 0.4, 0.35, 0.8], &[0, 0, 1, 1]), 0.75);
+        assert_eq!(roc_auc(&[0.9, 0.8, 0.2, 0.1], &[1, 1, 0, 0]), 1.0);
+        assert_eq!(roc_auc(&[0.5, 0.5, 0.5, 0.5], &[1, 0, 1, 0]), 0.5);
+        assert_eq!(roc_auc(&[0.3, 0.7], &[0, 0]), 0.5);
+    }
+
+    #[test]
+    fn test_pr_auc() {
+        assert_eq!(pr_auc(&[0.9, 0.8, 0.2, 0.1], &[1, 1, 0, 0]), 1.0);
+        // Ranked: 1, 0, 1 -> AP = 0.5 * 1 + 0.5 * 2/3.
+        let ap = pr_auc(&[0.9, 0.5, 0.4], &[1, 0, 1]);
+        assert!((ap - (0.5 + 1.0 / 3.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_calibration_table() {
+        let scores = [0.05, 0.15, 0.85, 0.95, 1.0];
+        let labels = [0, 1, 1, 1, 1];
+        let table = calibration_table(&scores, &labels, 10);
+        assert_eq!(table.len(), 10);
+        assert_eq!(table[0].count, 1);
+        assert_eq!(table[1].positive_rate, 1.0);
+        assert_eq!(table[9].count, 2);
+        assert!((table[9].mean_score - 0.975).abs() < 1e-6);
+        let total: usize = table.iter().map(|b| b.count).sum();
+        assert_eq!(total, 5);
+    }
+}
@@ -0,0 +1,277 @@
+//! Checks the analytic gradients of `compute_gradients` against central
+//! finite differences `(L(x + h) - L(x - h)) / 2h`.
+
+use crate::loss::Loss;
+use crate::matrix::Matrix;
+use crate::model::Model;
+use crate::train::compute_gradients;
+
+/// Step used for the finite differences. Smaller steps drown in f32
+/// rounding of the loss.
+pub const DEFAULT_STEP: f32 = 1e-2;
+
+/// Gradients smaller than this are compared by absolute rather than
+/// relative error, since finite differences in f32 cannot resolve them.
+const ERROR_FLOOR: f32 = 1e-2;
+
+/// Largest relative error found in each layer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GradCheckReport {
+    /// Loss of the unperturbed model.
+    pub loss: f32,
+    /// One entry per layer, in layer order, covering the gradients of the
+    /// layer's parameters and of its input.
+    pub layer_errors: Vec<f32>,
+}
+
+impl GradCheckReport {
+    pub fn max_error(&self) -> f32 {
+        self.layer_errors.iter().cloned().fold(0.0, f32::max)
+    }
+
+    pub fn print(&self) {
+        println!("🧮 Gradient check (loss {:.6}):", self.loss);
+        for (i, error) in self.layer_errors.iter().enumerate() {
+            println!("  Layer {:>2}: max relative error {:.2e}", i, error);
+        }
+    }
+}
+
+/// `|a - n| / max(|a|, |n|)`, floored so tiny gradients are not blown up.
+pub fn relative_error(analytic: f32, numeric: f32) -> f32 {
+    (analytic - numeric).abs() / analytic.abs().max(numeric.abs()).max(ERROR_FLOOR)
+}
+
+/// Compares every gradient `train_step` would compute for this batch with
+/// finite differences of `loss_fn` taken with step `step`, in the model's
+/// current mode. Every pass runs on a copy of the model starting from the
+/// same RNG state, so in training mode all passes share one set of dropout
+/// masks, and batch norm's running statistics are left alone.
+pub fn check_gradients(
+    model: &Model,
+    input: &Matrix,
+    target: &Matrix,
+    loss_fn: Loss,
+    step: f32,
+) -> GradCheckReport {
+    let mut probe = model.clone();
+    let (loss, input_grads) =
+        compute_gradients(&mut probe, input, target, loss_fn, model.is_training());
+    let param_grads: Vec<Vec<Option<Matrix>>> = probe
+        .layers_mut()
+        .map(|layer| {
+            layer
+                .params_mut()
+                .into_iter()
+                .map(|(_, grad)| grad.cloned())
+                .collect()
+        })
+        .collect();
+
+    let mut layer_errors = Vec::with_capacity(param_grads.len());
+    for (l, (grads, input_grad)) in param_grads.iter().zip(&input_grads).enumerate() {
+        let mut max_error: f32 = 0.0;
+
+        for (k, grad) in grads.iter().enumerate() {
+            let grad = grad.as_ref().expect("backward sets every gradient");
+            for i in 0..grad.as_slice().len() {
+                let numeric = central_difference(step, |delta| {
+                    let mut perturbed = model.clone();
+                    *param_mut(&mut perturbed, l, k, i) += delta;
+                    loss_fn.compute(&nudged_output(perturbed, input, None), target)
+                });
+                max_error = max_error.max(relative_error(grad.as_slice()[i], numeric));
+            }
+        }
+
+        for i in 0..input_grad.as_slice().len() {
+            let numeric = central_difference(step, |delta| {
+                let output = nudged_output(model.clone(), input, Some((l, i, delta)));
+                loss_fn.compute(&output, target)
+            });
+            max_error = max_error.max(relative_error(input_grad.as_slice()[i], numeric));
+        }
+
+        layer_errors.push(max_error);
+    }
+
+    GradCheckReport { loss, layer_errors }
+}
+
+fn central_difference(step: f32, mut loss_at: impl FnMut(f32) -> f32) -> f32 {
+    (loss_at(step) - loss_at(-step)) / (2.0 * step)
+}
+
+/// Output of the forward pass `compute_gradients` runs, with `delta` added
+/// to element `i` of the input of layer `l` when `nudge` is
+/// `Some((l, i, delta))`.
+fn nudged_output(mut model: Model, input: &Matrix, nudge: Option<(usize, usize, f32)>) -> Matrix {
+    let training = model.is_training();
+    let mut rng = model.rng_mut().clone();
+    let mut x = input.clone();
+    for (l, layer) in model.layers_mut().enumerate() {
+        if let Some((_, i, delta)) = nudge.filter(|&(target, _, _)| target == l) {
+            x.as_mut_slice()[i] += delta;
+        }
+        x = layer.forward(&x, training, &mut rng);
+    }
+    x
+}
+
+/// Element `i` of parameter `k` of layer `l`.
+fn param_mut(model: &mut Model, l: usize, k: usize, i: usize) -> &mut f32 {
+    let layer = model.layers_mut().nth(l).expect("layer exists");
+    let (param, _) = layer.params_mut().swap_remove(k);
+    &mut param.as_mut_slice()[i]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loss::ClassWeights;
+    use crate::spec::parse_spec;
+
+    /// Error allowed between analytic and numeric gradients.
+    const TOLERANCE: f32 = 1e-2;
+
+    fn batch(outputs: usize) -> (Matrix, Matrix) {
+        let input = Matrix::from_fn(4, 3, |r, c| ((r * 3 + c * 5) % 7) as f32 / 7.0 - 0.4);
+        let target = Matrix::from_fn(4, outputs, |r, c| {
+            if outputs == 1 {
+                (r % 2) as f32
+            } else {
+                (r % outputs == c) as u8 as f32
+            }
+        });
+        (input, target)
+    }
+
+    fn assert_gradients_match(spec: &str, loss: Loss) {
+        // With this seed no ReLU input lies within a step of the kink at 0,
+        // where finite differences and the derivative disagree.
+        let model = parse_spec(spec, 4).unwrap();
+        assert_model_gradients_match(&model, spec, loss, 1.0, DEFAULT_STEP);
+    }
+
+    fn assert_model_gradients_match(
+        model: &Model,
+        spec: &str,
+        loss: Loss,
+        input_scale: f32,
+        step: f32,
+    ) {
+        let before = crate::storage::model_to_string(model);
+        let (input, target) = batch(model.output_size());
+        let input = input.scale(input_scale);
+        let report = check_gradients(model, &input, &target, loss, step);
+        assert_eq!(report.layer_errors.len(), model.layers().len());
+        assert!(
+            report.max_error() < TOLERANCE,
+            "{} with {}: {:?}",
+            spec,
+            loss.name(),
+            report.layer_errors
+        );
+        assert_eq!(crate::storage::model_to_string(model), before);
+    }
+
+    #[test]
+    fn test_every_activation() {
+        for activation in [
+            "sigmoid",
+            "relu",
+            "leaky_relu:0.1",
+            "tanh",
+            "softplus",
+            "gelu",
+            "identity",
+        ] {
+            let spec = format!("3-d5-{}-d2-tanh", activation);
+            assert_gradients_match(&spec, Loss::MeanSquaredError);
+        }
+    }
+
+    #[test]
+    fn test_softmax_with_and_without_fused_gradient() {
+        assert_gradients_match("3-d4-tanh-d3-softmax", Loss::CategoricalCrossEntropy);
+        assert_gradients_match("3-d4-tanh-d3-softmax", Loss::MeanSquaredError);
+    }
+
+    #[test]
+    fn test_binary_losses() {
+        let weights = ClassWeights {
+            negative: 0.5,
+            positive: 2.0,
+        };
+        for loss in [
+            Loss::BinaryCrossEntropy {
+                weights,
+                from_logits: false,
+            },
+            Loss::Focal {
+                gamma: 2.0,
+                weights,
+            },
+        ] {
+            assert_gradients_match("3-d4-sigmoid-d1-sigmoid", loss);
+            // Without a final sigmoid the loss gradient is not fused.
+            assert_gradients_match("3-d4-sigmoid-d1-sigmoid-identity", loss);
+        }
+        let logits = Loss::BinaryCrossEntropy {
+            weights,
+            from_logits: true,
+        };
+        assert_gradients_match("3-d4-gelu-d1-identity", logits);
+    }
+
+    #[test]
+    fn test_normalization_layers() {
+        // Normalizing a batch of four divides by a small spread for some
+        // features, so the loss curves sharply: use wider inputs and a
+        // smaller step.
+        let spec = "3-d4-batchnorm-tanh-d3-layernorm-softmax";
+        let loss = Loss::CategoricalCrossEntropy;
+        let mut model = parse_spec(spec, 4).unwrap();
+        model.train();
+        assert_model_gradients_match(&model, spec, loss, 5.0, 3e-3);
+
+        // Give batch norm running statistics that differ from the identity
+        // before checking the inference-mode gradients.
+        model.forward_train(&batch(3).0.scale(3.0));
+        model.eval();
+        assert_model_gradients_match(&model, spec, loss, 5.0, 3e-3);
+        assert_gradients_match("3-layernorm-d4-sigmoid-d1-sigmoid", Loss::MeanSquaredError);
+    }
+
+    #[test]
+    fn test_dropout_in_training_mode() {
+        let spec = "3-d6-tanh-dropout:0.3-d2-tanh";
+        let mut model = parse_spec(spec, 4).unwrap();
+        model.train();
+        assert_model_gradients_match(&model, spec, Loss::MeanSquaredError, 1.0, DEFAULT_STEP);
+    }
+
+    #[test]
+    fn test_reports_the_relu_kink() {
+        use crate::activation::Activation;
+        use crate::layer::{ActivationLayer, DenseLayer, LayerEnum};
+
+        // At 0 the ReLU derivative is taken as 0, but a central difference
+        // sees half the slope on each side.
+        let mut model = Model::new();
+        model.add_layer(LayerEnum::Dense(DenseLayer::from_parts(
+            Matrix::ones(1, 1),
+            Matrix::zeros(1, 1),
+        )));
+        model.add_layer(LayerEnum::Activation(ActivationLayer::new(
+            Activation::Relu,
+        )));
+        let input = Matrix::zeros(1, 1);
+        let target = Matrix::ones(1, 1);
+        let loss = Loss::MeanSquaredError;
+        let report = check_gradients(&model, &input, &target, loss, DEFAULT_STEP);
+        assert!(report.layer_errors[1] > 0.9, "{:?}", report.layer_errors);
+        assert_eq!(relative_error(2.0, 1.0), 0.5);
+        assert!((relative_error(0.0, 0.001) - 0.1).abs() < 1e-6);
+    }
+}
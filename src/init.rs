@@ -0,0 +1,224 @@
+use crate::matrix::Matrix;
+use rand::Rng;
+use std::f32::consts::PI;
+use std::fmt;
+
+/// How the weights of a dense layer are drawn. `fan_in` and `fan_out` are
+/// the layer's input and output widths.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Initializer {
+    /// Uniform on (-1, 1) whatever the layer size.
+    Uniform,
+    /// Glorot: uniform on ±sqrt(6 / (fan_in + fan_out)).
+    XavierUniform,
+    /// Glorot: normal with std sqrt(2 / (fan_in + fan_out)).
+    XavierNormal,
+    /// Kaiming, for ReLU-like activations: uniform on ±sqrt(6 / fan_in).
+    HeUniform,
+    /// Kaiming: normal with std sqrt(2 / fan_in).
+    HeNormal,
+    /// Normal with std sqrt(1 / fan_in).
+    LeCun,
+    /// A random matrix with orthonormal rows or columns, whichever are fewer.
+    Orthogonal,
+    /// Every weight set to the same value.
+    Constant(f32),
+}
+
+impl Initializer {
+    /// A `rows` x `cols` weight matrix, i.e. fan-in `rows` and fan-out `cols`.
+    pub fn weights(&self, rows: usize, cols: usize, rng: &mut impl Rng) -> Matrix {
+        let fan_in = rows as f32;
+        let fan_out = cols as f32;
+        match *self {
+            Initializer::Uniform => fill(rows, cols, || rng.gen_range(-1.0..1.0)),
+            Initializer::XavierUniform => {
+                let limit = (6.0 / (fan_in + fan_out)).sqrt();
+                fill(rows, cols, || rng.gen_range(-limit..limit))
+            }
+            Initializer::XavierNormal => {
+                let std = (2.0 / (fan_in + fan_out)).sqrt();
+                fill(rows, cols, || std * standard_normal(rng))
+            }
+            Initializer::HeUniform => {
+                let limit = (6.0 / fan_in).sqrt();
+                fill(rows, cols, || rng.gen_range(-limit..limit))
+            }
+            Initializer::HeNormal => {
+                let std = (2.0 / fan_in).sqrt();
+                fill(rows, cols, || std * standard_normal(rng))
+            }
+            Initializer::LeCun => {
+                let std = (1.0 / fan_in).sqrt();
+                fill(rows, cols, || std * standard_normal(rng))
+            }
+            Initializer::Orthogonal => orthogonal(rows, cols, rng),
+            Initializer::Constant(value) => fill(rows, cols, || value),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Initializer::Uniform => "uniform",
+            Initializer::XavierUniform => "xavier_uniform",
+            Initializer::XavierNormal => "xavier_normal",
+            Initializer::HeUniform => "he_uniform",
+            Initializer::HeNormal => "he_normal",
+            Initializer::LeCun => "lecun",
+            Initializer::Orthogonal => "orthogonal",
+            Initializer::Constant(_) => "constant",
+        }
+    }
+
+    /// Parses the `Display` form. `constant` takes its value as
+    /// `constant:0.1`.
+    pub fn from_name(name: &str) -> Option<Initializer> {
+        let initializer = match name {
+            "uniform" => Initializer::Uniform,
+            "xavier_uniform" => Initializer::XavierUniform,
+            "xavier_normal" => Initializer::XavierNormal,
+            "he_uniform" => Initializer::HeUniform,
+            "he_normal" => Initializer::HeNormal,
+            "lecun" => Initializer::LeCun,
+            "orthogonal" => Initializer::Orthogonal,
+            _ => Initializer::Constant(name.strip_prefix("constant:")?.parse().ok()?),
+        };
+        Some(initializer)
+    }
+}
+
+impl fmt::Display for Initializer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Initializer::Constant(value) => write!(f, "{}:{}", self.name(), value),
+            _ => write!(f, "{}", self.name()),
+        }
+    }
+}
+
+fn fill(rows: usize, cols: usize, mut value: impl FnMut() -> f32) -> Matrix {
+    Matrix::from_vec(
+        (0..rows)
+            .map(|_| (0..cols).map(|_| value()).collect())
+            .collect(),
+    )
+}
+
+/// A draw from N(0, 1) by the Box-Muller transform.
+fn standard_normal(rng: &mut impl Rng) -> f32 {
+    // 1 - u keeps the logarithm away from zero.
+    let u1: f32 = 1.0 - rng.gen::<f32>();
+    let u2: f32 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
+
+/// Gram-Schmidt on Gaussian vectors along the longer side, so the shorter
+/// side ends up orthonormal.
+fn orthogonal(rows: usize, cols: usize, rng: &mut impl Rng) -> Matrix {
+    let (count, len) = (rows.min(cols), rows.max(cols));
+    let mut vectors: Vec<Vec<f32>> = Vec::with_capacity(count);
+    while vectors.len() < count {
+        let mut v: Vec<f32> = (0..len).map(|_| standard_normal(rng)).collect();
+        for u in &vectors {
+            let dot: f32 = v.iter().zip(u).map(|(a, b)| a * b).sum();
+            v.iter_mut().zip(u).for_each(|(a, b)| *a -= dot * b);
+        }
+        let norm = v.iter().map(|a| a * a).sum::<f32>().sqrt();
+        // A nearly dependent draw would amplify rounding errors; draw again.
+        if norm > 1e-3 {
+            vectors.push(v.iter().map(|a| a / norm).collect());
+        }
+    }
+
+    let mut m = Matrix::new(rows, cols);
+    for (i, v) in vectors.iter().enumerate() {
+        for (j, &value) in v.iter().enumerate() {
+            if rows >= cols {
+                m.set(j, i, value);
+            } else {
+                m.set(i, j, value);
+            }
+        }
+    }
+    m
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn values(m: &Matrix) -> Vec<f32> {
+        (0..m.rows()).flat_map(|r| m.row(r).to_vec()).collect()
+    }
+
+    fn std_dev(v: &[f32]) -> f32 {
+        let mean = v.iter().sum::<f32>() / v.len() as f32;
+        (v.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / v.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn test_scales_follow_fan_in_and_fan_out() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let (rows, cols) = (200, 100);
+        let cases = [
+            (Initializer::XavierUniform, (2.0 / 300.0f32).sqrt()),
+            (Initializer::XavierNormal, (2.0 / 300.0f32).sqrt()),
+            (Initializer::HeUniform, (2.0 / 200.0f32).sqrt()),
+            (Initializer::HeNormal, (2.0 / 200.0f32).sqrt()),
+            (Initializer::LeCun, (1.0 / 200.0f32).sqrt()),
+        ];
+        for (init, expected) in cases {
+            let std = std_dev(&values(&init.weights(rows, cols, &mut rng)));
+            assert!(
+                (std - expected).abs() < 0.05 * expected,
+                "{}: std {}",
+                init,
+                std
+            );
+        }
+
+        let limit = (6.0 / 300.0f32).sqrt();
+        let w = values(&Initializer::XavierUniform.weights(rows, cols, &mut rng));
+        assert!(w.iter().all(|x| x.abs() <= limit));
+    }
+
+    #[test]
+    fn test_orthogonal_has_orthonormal_short_side() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for (rows, cols) in [(8, 3), (3, 8), (5, 5)] {
+            let w = Initializer::Orthogonal.weights(rows, cols, &mut rng);
+            let gram = if rows >= cols {
+                w.transpose().dot(&w)
+            } else {
+                w.dot(&w.transpose())
+            };
+            for i in 0..gram.rows() {
+                for j in 0..gram.cols() {
+                    let expected = if i == j { 1.0 } else { 0.0 };
+                    assert!((gram.get(i, j) - expected).abs() < 1e-4);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_constant_and_names() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let w = Initializer::Constant(0.25).weights(2, 3, &mut rng);
+        assert!(values(&w).iter().all(|&x| x == 0.25));
+
+        for init in [
+            Initializer::Uniform,
+            Initializer::XavierNormal,
+            Initializer::HeUniform,
+            Initializer::Orthogonal,
+            Initializer::Constant(0.5),
+        ] {
+            assert_eq!(Initializer::from_name(&init.to_string()), Some(init));
+        }
+        assert_eq!(Initializer::from_name("constant"), None);
+        assert_eq!(Initializer::from_name("zeros"), None);
+    }
+}
@@ -1,14 +1,31 @@
-use crate::dataset::generate_dataset;
-use crate::matrix::Matrix;
-use crate::train::{debug_forward_sample, train_step};
+use crate::dataset::{
+    generate_dataset, select, split_dataset, SplitConfig, SplitStrategy, Task, INPUT_BITS,
+};
+use crate::evaluation::{
+    best_threshold, binary_scores, calibration_table, class_counts, pr_auc,
+    print_calibration_table, roc_auc, threshold_sweep, BinaryMetrics, ThresholdCriterion,
+};
+use crate::init::Initializer;
+use crate::loss::{ClassWeights, Loss, DEFAULT_FOCAL_GAMMA};
+use crate::train::{
+    append_logit_sigmoid, check_batch_size, debug_forward_sample, train_epochs, TrainConfig,
+};
 use std::io::{self, Write};
 
-use crate::activation::sigmoid;
-use crate::layer::{ActivationLayer, DenseLayer, LayerEnum};
-use crate::model::Model;
+use crate::model::{Model, DEFAULT_SEED};
+use crate::optimizer::OptimizerKind;
+use crate::regularization::Regularization;
+use crate::schedule::LrSchedule;
+use crate::spec::{default_spec, model_spec, parse_spec};
+use crate::storage::{load_model, save_model};
+use std::path::Path;
+
+const DEFAULT_MODEL_PATH: &str = "model.txt";
 
 pub fn run_ui() {
-    let mut model = build_model();
+    let mut task = Task::Primality;
+    let mut model = build_model(task, DEFAULT_SEED);
+    let mut split = SplitConfig::default();
 
     loop {
         println!("\n🤖 Simplest AI Interface");
@@ -17,67 +34,411 @@ pub fn run_ui() {
         println!("3. Evaluate performance");
         println!("4. View model weights");
         println!("5. Edit model weights");
-        println!("6. Quit");
+        println!("6. Save model");
+        println!("7. Load model");
+        println!("8. Choose task (current: {})", task.name());
+        println!(
+            "9. Configure data split (current: {} {:.0}/{:.0}/{:.0})",
+            split.strategy.name(),
+            100.0 * split.train_fraction,
+            100.0 * split.validation_fraction,
+            100.0 * (1.0 - split.train_fraction - split.validation_fraction)
+        );
+        println!("10. Decision threshold (current: {:.2})", model.threshold());
+        println!(
+            "11. Build model from spec (current: {})",
+            model_spec(&model)
+        );
+        println!("12. Random seed (current: {})", model.seed());
+        println!("13. Quit");
         print!("Choose an option: ");
         io::stdout().flush().unwrap();
 
         let mut choice = String::new();
         io::stdin().read_line(&mut choice).unwrap();
         match choice.trim() {
-            "1" => train_menu(&mut model),
-            "2" => infer_menu(&mut model),
-            "3" => evaluate_menu(&mut model),
+            "1" => train_menu(&mut model, task, &split),
+            "2" => infer_menu(&model, task),
+            "3" => evaluate_menu(&model, task, &split),
             "4" => model.print_weights(),
             "5" => edit_weights_menu(&mut model),
-            "6" => break,
+            "6" => save_menu(&model),
+            "7" => load_menu(&mut model, task, &mut split),
+            "8" => task_menu(&mut model, &mut task),
+            "9" => split_menu(&mut split),
+            "10" => threshold_menu(&mut model, task, &split),
+            "11" => spec_menu(&mut model, task),
+            "12" => seed_menu(&mut model, &mut split),
+            "13" => break,
             _ => println!("Invalid option. Try again."),
         }
     }
 }
 
-fn build_model() -> Model {
-    let mut model = Model::new();
-    model.add_layer(LayerEnum::Dense(DenseLayer::new(10, 8)));
-    model.add_layer(LayerEnum::Activation(ActivationLayer::new(sigmoid)));
-    model.add_layer(LayerEnum::Dense(DenseLayer::new(8, 8)));
-    model.add_layer(LayerEnum::Activation(ActivationLayer::new(sigmoid)));
-    model.add_layer(LayerEnum::Dense(DenseLayer::new(8, 8)));
-    model.add_layer(LayerEnum::Activation(ActivationLayer::new(sigmoid)));
-    model.add_layer(LayerEnum::Dense(DenseLayer::new(8, 8)));
-    model.add_layer(LayerEnum::Activation(ActivationLayer::new(sigmoid)));
-    model.add_layer(LayerEnum::Dense(DenseLayer::new(8, 1)));
-    model.add_layer(LayerEnum::Activation(ActivationLayer::new(sigmoid)));
-    model
+fn build_model(task: Task, seed: u64) -> Model {
+    parse_spec(&default_spec(task), seed).expect("default spec is valid")
 }
 
-fn train_menu(model: &mut Model) {
-    let mut input = String::new();
-    print!("\n📚 Enter number of training samples (1-1024): ");
-    io::stdout().flush().unwrap();
-    io::stdin().read_line(&mut input).unwrap();
-    let count: usize = input.trim().parse().unwrap_or(0);
-    if count == 0 || count > 1024 {
-        println!("⚠️ Invalid sample count.");
+/// Asks for the loss on binary tasks; multi-class tasks always use
+/// cross-entropy on the softmax output.
+fn choose_loss(task: Task, labels: &[u8]) -> Loss {
+    if task.num_classes() > 1 {
+        return Loss::CategoricalCrossEntropy;
+    }
+
+    println!("📐 Loss:");
+    println!("1. Mean squared error");
+    println!("2. Binary cross-entropy");
+    println!("3. Focal loss");
+    println!("4. Binary cross-entropy from logits (model must end in a dense layer)");
+    let choice = read_or_default("Choose a loss", 1);
+    if !(2..=4).contains(&choice) {
+        return Loss::MeanSquaredError;
+    }
+
+    let balanced = ClassWeights::balanced(labels);
+    println!("⚖️ Class weights:");
+    println!("1. None");
+    println!(
+        "2. Balanced (negative {:.3}, positive {:.3})",
+        balanced.negative, balanced.positive
+    );
+    println!("3. Custom");
+    let weights = match read_or_default("Choose class weights", 2) {
+        1 => ClassWeights::UNIFORM,
+        3 => ClassWeights::new(
+            read_or_default("Enter negative class weight", 1.0),
+            read_or_default("Enter positive class weight", 1.0),
+        )
+        .unwrap_or_else(|e| {
+            println!("⚠️ {}. Using balanced weights.", e);
+            balanced
+        }),
+        _ => balanced,
+    };
+
+    if choice == 3 {
+        let gamma = read_or_default("Enter focal gamma", DEFAULT_FOCAL_GAMMA);
+        Loss::focal(gamma, weights).unwrap_or_else(|e| {
+            println!("⚠️ {}. Using {}.", e, DEFAULT_FOCAL_GAMMA);
+            Loss::Focal {
+                gamma: DEFAULT_FOCAL_GAMMA,
+                weights,
+            }
+        })
+    } else {
+        Loss::BinaryCrossEntropy {
+            weights,
+            from_logits: choice == 4,
+        }
+    }
+}
+
+fn task_menu(model: &mut Model, task: &mut Task) {
+    println!("\n🎯 Tasks:");
+    println!("1. Primality (prime or not)");
+    println!("2. n mod k");
+    println!("3. Number of prime factors");
+    println!("4. Smallest prime factor");
+    let new_task = match read_or_default("Choose a task", 1) {
+        1 => Task::Primality,
+        2 => {
+            let k: u16 = read_or_default("Enter k (2-64)", 3);
+            if !(2..=64).contains(&k) {
+                println!("⚠️ k must be between 2 and 64.");
+                return;
+            }
+            Task::Modulo(k)
+        }
+        3 => Task::PrimeFactorCount,
+        4 => Task::SmallestPrimeFactor,
+        _ => {
+            println!("⚠️ Invalid task.");
+            return;
+        }
+    };
+    *task = new_task;
+    *model = build_model(new_task, model.seed());
+    println!(
+        "🆕 Built a new model for {} with {} output(s).",
+        new_task.name(),
+        new_task.num_classes()
+    );
+}
+
+/// Warns and returns false when the model cannot read the input bits or its
+/// outputs do not match `task`, so the menus never run a mismatched model.
+fn model_fits(model: &Model, task: Task) -> bool {
+    if model.input_size() != INPUT_BITS {
+        println!(
+            "⚠️ The model takes {} input(s) but numbers have {} bits; build or load a matching model.",
+            model.input_size(),
+            INPUT_BITS
+        );
+        return false;
+    }
+    if model.output_size() != task.num_classes() {
+        println!(
+            "⚠️ The model has {} output(s) but {} expects {}; choose a matching task.",
+            model.output_size(),
+            task.name(),
+            task.num_classes()
+        );
+        return false;
+    }
+    true
+}
+
+fn train_menu(model: &mut Model, task: Task, split_config: &SplitConfig) {
+    if !model_fits(model, task) {
         return;
     }
+    let data = generate_dataset(task);
+    let split = split_dataset(&data, split_config);
+    let train = select(&data, &split.train);
+    let validation = select(&data, &split.validation);
+    println!(
+        "\n📚 Training on {} samples, validating on {} ({} split).",
+        train.len(),
+        validation.len(),
+        split_config.strategy.name()
+    );
+    if train.is_empty() {
+        println!("⚠️ The training split is empty.");
+        return;
+    }
+    let labels: Vec<u8> = train.iter().map(|(_, y)| *y).collect();
 
-    let data = generate_dataset();
-    let learning_rate = 0.1;
+    let loss = choose_loss(task, &labels);
+    if let Err(e) = loss.check_output(model.layers().last()) {
+        println!("⚠️ {}.", e);
+        return;
+    }
+    let optimizer = choose_optimizer();
+    let defaults = TrainConfig::default();
+    let config = TrainConfig {
+        learning_rate: read_or_default("📈 Enter learning rate", optimizer.default_learning_rate()),
+        schedule: choose_schedule(),
+        optimizer,
+        loss,
+        epochs: read_or_default("🔁 Enter number of epochs", defaults.epochs),
+        batch_size: read_or_default("📦 Enter batch size", defaults.batch_size),
+        threads: read_or_default("🧵 Enter worker threads per batch", defaults.threads),
+        regularization: choose_regularization(),
+        seed: model.seed(),
+        verbose: true,
+    };
+    if config.epochs == 0 || config.batch_size == 0 || config.threads == 0 {
+        println!("⚠️ Epochs, batch size and threads must be at least 1.");
+        return;
+    }
+    if let Err(e) = check_batch_size(model, config.batch_size) {
+        println!("⚠️ {}.", e);
+        return;
+    }
 
-    let mut total_loss = 0.0;
-    for (x, y) in data.iter().take(count) {
-        let input = Matrix::from_vec(vec![x.iter().map(|&b| b as f32).collect()]);
-        let target = Matrix::from_vec(vec![vec![*y as f32]]);
-        total_loss += train_step(model, &input, &target, learning_rate);
+    println!(
+        "🏋️ Training on {} with {} and {} loss (learning rate {}, {} schedule).",
+        task.name(),
+        config.optimizer.name(),
+        config.loss.name(),
+        config.learning_rate,
+        config.schedule
+    );
+    let history = train_epochs(model, &train, &validation, &config);
+    if append_logit_sigmoid(model, config.loss) {
+        println!("➕ Appended a sigmoid so predictions are probabilities again.");
     }
 
     println!(
-        "✅ Training complete. Avg loss: {:.6}",
-        total_loss / count as f32
+        "✅ Training complete. Final avg loss: {:.6}",
+        history.epoch_losses.last().copied().unwrap_or(0.0)
     );
+    if let Some(validation_loss) = history.validation_losses.last() {
+        println!("🧪 Final validation loss: {:.6}", validation_loss);
+    }
+}
+
+fn split_menu(split: &mut SplitConfig) {
+    println!("\n✂️ Split strategy:");
+    println!("1. Random");
+    println!("2. Stratified by label");
+    println!("3. Range (numbers in order)");
+    let strategy = match read_or_default("Choose a strategy", 2) {
+        1 => SplitStrategy::Random,
+        3 => SplitStrategy::Range,
+        _ => SplitStrategy::Stratified,
+    };
+    let train_fraction: f32 = read_or_default("Enter training fraction", split.train_fraction);
+    let validation_fraction: f32 =
+        read_or_default("Enter validation fraction", split.validation_fraction);
+    if train_fraction <= 0.0
+        || validation_fraction < 0.0
+        || train_fraction + validation_fraction > 1.0
+    {
+        println!("⚠️ Fractions must be non-negative and add up to at most 1.");
+        return;
+    }
+    *split = SplitConfig {
+        strategy,
+        train_fraction,
+        validation_fraction,
+        seed: split.seed,
+    };
+    println!("✅ Split updated.");
+}
+
+/// Asks which split to score and returns its sample indices.
+fn choose_split(data: &[(Vec<u8>, u8)], split_config: &SplitConfig) -> (&'static str, Vec<usize>) {
+    println!("🗂️ Evaluate on:");
+    println!("1. Training set");
+    println!("2. Validation set");
+    println!("3. Test set");
+    println!("4. All 1024 numbers");
+    let split = split_dataset(data, split_config);
+    match read_or_default("Choose a set", 3) {
+        1 => ("training", split.train),
+        2 => ("validation", split.validation),
+        4 => ("all", (0..data.len()).collect()),
+        _ => ("test", split.test),
+    }
+}
+
+fn choose_optimizer() -> OptimizerKind {
+    println!("⚙️ Optimizer:");
+    println!("1. SGD");
+    println!("2. SGD with momentum");
+    println!("3. SGD with Nesterov momentum");
+    println!("4. RMSProp");
+    println!("5. Adam");
+    match read_or_default("Choose an optimizer", 1) {
+        2 => OptimizerKind::momentum(),
+        3 => OptimizerKind::nesterov(),
+        4 => OptimizerKind::rms_prop(),
+        5 => OptimizerKind::adam(),
+        _ => OptimizerKind::Sgd,
+    }
+}
+
+fn choose_schedule() -> LrSchedule {
+    println!("📐 Learning-rate schedule:");
+    println!("1. Constant");
+    println!("2. Step decay");
+    println!("3. Exponential decay");
+    println!("4. Cosine annealing with warm restarts");
+    println!("5. Linear warmup");
+    println!("6. Reduce on validation plateau");
+    let schedule = match read_or_default("Choose a schedule", 1) {
+        2 => LrSchedule::step_decay(),
+        3 => LrSchedule::exponential(),
+        4 => LrSchedule::cosine_warm_restarts(),
+        5 => LrSchedule::linear_warmup(),
+        6 => LrSchedule::reduce_on_plateau(),
+        _ => LrSchedule::Constant,
+    };
+    let schedule = match schedule {
+        LrSchedule::StepDecay { step_size, gamma } => LrSchedule::StepDecay {
+            step_size: read_or_default("Enter epochs between decays", step_size),
+            gamma: read_or_default("Enter decay factor", gamma),
+        },
+        LrSchedule::Exponential { gamma } => LrSchedule::Exponential {
+            gamma: read_or_default("Enter decay factor per epoch", gamma),
+        },
+        LrSchedule::CosineWarmRestarts { period, multiplier } => LrSchedule::CosineWarmRestarts {
+            period: read_or_default("Enter epochs in the first cycle", period),
+            multiplier: read_or_default("Enter cycle length multiplier", multiplier),
+        },
+        LrSchedule::LinearWarmup { epochs } => LrSchedule::LinearWarmup {
+            epochs: read_or_default("Enter warmup epochs", epochs),
+        },
+        LrSchedule::ReduceOnPlateau { factor, patience } => LrSchedule::ReduceOnPlateau {
+            factor: read_or_default("Enter reduction factor", factor),
+            patience: read_or_default("Enter patience in epochs", patience),
+        },
+        LrSchedule::Constant => LrSchedule::Constant,
+    };
+    // Reuse the parser's checks on the parameters.
+    LrSchedule::from_name(&schedule.to_string()).unwrap_or_else(|| {
+        println!("⚠️ Invalid schedule parameters, using a constant rate.");
+        LrSchedule::Constant
+    })
+}
+
+/// Asks for penalties and clipping limits; 0 turns each one off.
+fn choose_regularization() -> Regularization {
+    print!("🧲 Configure weight decay and gradient clipping? (y/N): ");
+    io::stdout().flush().unwrap();
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).unwrap();
+    if !answer.trim().eq_ignore_ascii_case("y") {
+        return Regularization::default();
+    }
+    let limit = |value: f32| Some(value).filter(|&v| v > 0.0);
+    Regularization {
+        l1: read_or_default("Enter L1 penalty", 0.0f32).max(0.0),
+        l2: read_or_default("Enter L2 penalty (coupled)", 0.0f32).max(0.0),
+        weight_decay: read_or_default("Enter decoupled weight decay (AdamW)", 0.0f32).max(0.0),
+        clip_value: limit(read_or_default(
+            "Enter gradient clip value (0 for none)",
+            0.0,
+        )),
+        clip_norm: limit(read_or_default(
+            "Enter gradient clip norm (0 for none)",
+            0.0,
+        )),
+    }
+}
+
+fn choose_initializer() -> Initializer {
+    println!("🎲 Initializer:");
+    println!("1. Uniform (-1, 1)");
+    println!("2. Xavier/Glorot uniform");
+    println!("3. Xavier/Glorot normal");
+    println!("4. He uniform");
+    println!("5. He normal");
+    println!("6. LeCun normal");
+    println!("7. Orthogonal");
+    println!("8. Constant");
+    match read_or_default("Choose an initializer", 2) {
+        1 => Initializer::Uniform,
+        3 => Initializer::XavierNormal,
+        4 => Initializer::HeUniform,
+        5 => Initializer::HeNormal,
+        6 => Initializer::LeCun,
+        7 => Initializer::Orthogonal,
+        8 => Initializer::Constant(read_or_default("Enter weight value", 0.0)),
+        _ => Initializer::XavierUniform,
+    }
+}
+
+/// Prompts for a value, falling back to `default` on empty or invalid input.
+fn read_or_default<T: std::str::FromStr + std::fmt::Display>(prompt: &str, default: T) -> T {
+    print!("{} [{}]: ", prompt, default);
+    io::stdout().flush().unwrap();
+    let mut input = String::new();
+    io::stdin().read_line(&mut input).unwrap();
+    input.trim().parse().unwrap_or(default)
+}
+
+/// Prompts for a value with no default, reporting empty or invalid input
+/// instead of guessing.
+fn read_required<T: std::str::FromStr>(prompt: &str) -> Option<T> {
+    print!("{}: ", prompt);
+    io::stdout().flush().unwrap();
+    let mut input = String::new();
+    io::stdin().read_line(&mut input).unwrap();
+    let input = input.trim();
+    let value = input.parse().ok();
+    if value.is_none() {
+        println!("⚠️ '{}' is not a valid value. Weight not changed.", input);
+    }
+    value
 }
 
-fn infer_menu(model: &mut Model) {
+fn infer_menu(model: &Model, task: Task) {
+    if !model_fits(model, task) {
+        return;
+    }
     loop {
         print!("\n🔢 Enter a number (0-1023) or 'q' to quit: ");
         io::stdout().flush().unwrap();
@@ -96,9 +457,9 @@ fn infer_menu(model: &mut Model) {
                     .rev()
                     .map(|i| ((num >> i) & 1) as u8)
                     .collect::<Vec<_>>();
-                let is_prime = crate::dataset::is_prime(num as u16);
-                let label = if is_prime { 1 } else { 0 };
+                let label = task.label(num as u16);
                 debug_forward_sample(model, &binary_input, label, num);
+                println!("🏷️ Target means: {}", task.class_name(label as usize));
             } else {
                 println!("⚠️ Number out of range (0–1023).");
             }
@@ -108,46 +469,167 @@ fn infer_menu(model: &mut Model) {
     }
 }
 
-fn evaluate_menu(model: &mut Model) {
-    let data = generate_dataset();
-    let mut correct = 0;
-    let mut true_pos = vec![];
-    let mut false_pos = vec![];
-    let mut true_neg = vec![];
-    let mut false_neg = vec![];
-
-    for (i, (x, y)) in data.iter().enumerate() {
-        let input = Matrix::from_vec(vec![x.iter().map(|&b| b as f32).collect()]);
-        let output = model.forward(&input);
-        let prediction = output.get(0, 0);
-
-        if prediction >= 0.5 && *y == 1 {
-            correct += 1;
-            true_pos.push(i);
-        } else if prediction < 0.5 && *y == 0 {
-            correct += 1;
-            true_neg.push(i);
-        } else if prediction >= 0.5 && *y == 0 {
-            false_pos.push(i);
-        } else {
-            false_neg.push(i);
+fn evaluate_menu(model: &Model, task: Task, split_config: &SplitConfig) {
+    if !model_fits(model, task) {
+        return;
+    }
+    let data = generate_dataset(task);
+    let (set_name, indices) = choose_split(&data, split_config);
+    if indices.is_empty() {
+        println!("⚠️ The {} set is empty.", set_name);
+        return;
+    }
+    if task.num_classes() > 1 {
+        evaluate_classes(model, task, &data, &indices, set_name);
+        return;
+    }
+
+    let (scores, labels) = binary_scores(model, &data, &indices);
+    let metrics = BinaryMetrics::from_scores(&scores, &labels, model.threshold());
+    println!(
+        "\n📊 Evaluation Results ({} set, {} samples):",
+        set_name,
+        indices.len()
+    );
+    metrics.print();
+    println!("📈 ROC-AUC:           {:.4}", roc_auc(&scores, &labels));
+    println!("📈 PR-AUC:            {:.4}", pr_auc(&scores, &labels));
+    print_calibration_table(&calibration_table(&scores, &labels, 10));
+
+    print!("🔎 Show the numbers in each cell? (y/N): ");
+    io::stdout().flush().unwrap();
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).unwrap();
+    if answer.trim().eq_ignore_ascii_case("y") {
+        let cell = |predicted: bool, actual: u8| -> Vec<usize> {
+            indices
+                .iter()
+                .zip(scores.iter().zip(&labels))
+                .filter(|(_, (&score, &label))| {
+                    (score >= metrics.threshold) == predicted && label == actual
+                })
+                .map(|(&i, _)| i)
+                .collect()
+        };
+        println!("✔️ True Positives: {:?}", cell(true, 1));
+        println!("❌ False Positives: {:?}", cell(true, 0));
+        println!("✔️ True Negatives: {:?}", cell(false, 0));
+        println!("❌ False Negatives: {:?}", cell(false, 1));
+    }
+}
+
+fn threshold_menu(model: &mut Model, task: Task, split_config: &SplitConfig) {
+    if task.num_classes() > 1 {
+        println!("⚠️ Thresholds only apply to primality; other tasks pick the likeliest class.");
+        return;
+    }
+
+    println!(
+        "\n🎚️ Decision threshold (current: {:.2})",
+        model.threshold()
+    );
+    println!("1. Set manually");
+    println!("2. Sweep thresholds from 0 to 1");
+    match read_or_default("Choose an option", 2) {
+        1 => {
+            let threshold: f32 = read_or_default("Enter threshold (0-1)", model.threshold());
+            if !(0.0..=1.0).contains(&threshold) {
+                println!("⚠️ Threshold must be between 0 and 1.");
+                return;
+            }
+            model.set_threshold(threshold);
+            println!("✅ Threshold set to {:.2}.", threshold);
         }
+        2 => sweep_menu(model, task, split_config),
+        _ => println!("Invalid option."),
+    }
+}
+
+fn sweep_menu(model: &mut Model, task: Task, split_config: &SplitConfig) {
+    if !model_fits(model, task) {
+        return;
+    }
+    let data = generate_dataset(task);
+    let (set_name, indices) = choose_split(&data, split_config);
+    if indices.is_empty() {
+        println!("⚠️ The {} set is empty.", set_name);
+        return;
+    }
+    println!("🏁 Maximize:");
+    println!("1. F1");
+    println!("2. Balanced accuracy");
+    let criterion = match read_or_default("Choose a criterion", 1) {
+        2 => ThresholdCriterion::BalancedAccuracy,
+        _ => ThresholdCriterion::F1,
+    };
+
+    let (scores, labels) = binary_scores(model, &data, &indices);
+    let sweep = threshold_sweep(&scores, &labels, 100);
+    println!("\n  threshold  precision  recall      F1  balanced acc");
+    for m in sweep.iter().step_by(5) {
+        println!(
+            "  {:>9.2}  {:>9.4}  {:>6.4}  {:>6.4}  {:>12.4}",
+            m.threshold,
+            m.precision(),
+            m.recall(),
+            m.f1(),
+            m.balanced_accuracy()
+        );
+    }
+
+    let best = best_threshold(&sweep, criterion);
+    println!(
+        "🏆 Best {} on the {} set: {:.4} at threshold {:.2}",
+        criterion.name(),
+        set_name,
+        criterion.score(&best),
+        best.threshold
+    );
+    print!("Use this threshold? (Y/n): ");
+    io::stdout().flush().unwrap();
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).unwrap();
+    if !answer.trim().eq_ignore_ascii_case("n") {
+        model.set_threshold(best.threshold);
+        println!("✅ Threshold set to {:.2}.", best.threshold);
     }
+}
+
+fn evaluate_classes(
+    model: &Model,
+    task: Task,
+    data: &[(Vec<u8>, u8)],
+    indices: &[usize],
+    set_name: &str,
+) {
+    let classes = task.num_classes();
+    let (correct, totals) = class_counts(model, data, indices, classes);
 
-    let accuracy = 100.0 * correct as f32 / data.len() as f32;
-    println!("\n📊 Evaluation Results:");
+    let accuracy = 100.0 * correct.iter().sum::<usize>() as f32 / indices.len() as f32;
+    println!(
+        "\n📊 Evaluation Results ({}, {} set, {} samples):",
+        task.name(),
+        set_name,
+        indices.len()
+    );
     println!("✅ Accuracy: {:.2}%", accuracy);
-    println!("✔️ True Positives: {:?}", true_pos);
-    println!("❌ False Positives: {:?}", false_pos);
-    println!("✔️ True Negatives: {:?}", true_neg);
-    println!("❌ False Negatives: {:?}", false_neg);
+    for class in 0..classes {
+        if totals[class] > 0 {
+            println!(
+                "  {:<24} {:>4}/{:<4} correct",
+                task.class_name(class),
+                correct[class],
+                totals[class]
+            );
+        }
+    }
 }
 
 fn edit_weights_menu(model: &mut Model) {
     loop {
         println!("\n🛠️ Edit Weights Menu");
         println!("1. Manually edit specific weight");
-        println!("2. Randomize all weights");
+        println!("2. Initialize all weights");
         println!("3. Set all weights to zero");
         println!("4. Back to main menu");
         print!("Choose an option: ");
@@ -158,36 +640,33 @@ fn edit_weights_menu(model: &mut Model) {
         match input.trim() {
             "1" => {
                 println!("🔧 Manually editing a weight.");
-                print!("Enter Dense layer index: ");
-                io::stdout().flush().unwrap();
-                let mut idx = String::new();
-                io::stdin().read_line(&mut idx).unwrap();
-                let idx: usize = idx.trim().parse().unwrap_or(0);
-
-                print!("Enter weight row index: ");
-                io::stdout().flush().unwrap();
-                let mut row = String::new();
-                io::stdin().read_line(&mut row).unwrap();
-                let row: usize = row.trim().parse().unwrap_or(0);
-
-                print!("Enter weight column index: ");
-                io::stdout().flush().unwrap();
-                let mut col = String::new();
-                io::stdin().read_line(&mut col).unwrap();
-                let col: usize = col.trim().parse().unwrap_or(0);
-
-                print!("Enter new value: ");
-                io::stdout().flush().unwrap();
-                let mut val = String::new();
-                io::stdin().read_line(&mut val).unwrap();
-                let val: f32 = val.trim().parse().unwrap_or(0.0);
-
-                model.set_weight(idx, row, col, val);
-                println!("✅ Weight updated.");
+                let Some(idx) = read_required::<usize>("Enter Dense layer index") else {
+                    continue;
+                };
+                let Some(row) = read_required::<usize>("Enter weight row index") else {
+                    continue;
+                };
+                let Some(col) = read_required::<usize>("Enter weight column index") else {
+                    continue;
+                };
+                let Some(val) = read_required::<f32>("Enter new value") else {
+                    continue;
+                };
+                if !val.is_finite() {
+                    println!("⚠️ The new value must be a finite number. Weight not changed.");
+                    continue;
+                }
+
+                match model.set_weight(idx, row, col, val) {
+                    Ok(old) => println!("✅ Weight updated ({} → {}).", old, val),
+                    Err(e) => println!("⚠️ Could not set weight: {}", e),
+                }
             }
             "2" => {
-                model.randomize_all_weights();
-                println!("🎲 All weights randomized.");
+                let init = choose_initializer();
+                let bias: f32 = read_or_default("Enter bias value", 0.0);
+                model.initialize_weights(init, bias);
+                println!("🎲 Weights drawn with {}, biases set to {}.", init, bias);
             }
             "3" => {
                 model.zero_all_weights();
@@ -198,3 +677,80 @@ fn edit_weights_menu(model: &mut Model) {
         }
     }
 }
+
+fn spec_menu(model: &mut Model, task: Task) {
+    println!("Layers are separated by '-': the input width, then dN (dense with N outputs),");
+    println!("dNxM (dense with N inputs checked against the previous layer), softmax,");
+    println!("batchnorm, layernorm, dropout:0.2 (drops 20% of inputs while training), or");
+    println!("an activation (sigmoid, relu, leaky_relu:0.2, tanh, softplus, gelu, identity).");
+    print!("🏗️ Enter architecture spec: ");
+    io::stdout().flush().unwrap();
+    let mut input = String::new();
+    io::stdin().read_line(&mut input).unwrap();
+    if input.trim().is_empty() {
+        println!("Model unchanged.");
+        return;
+    }
+    match parse_spec(&input, model.seed()) {
+        Ok(built) => {
+            *model = built;
+            println!("🏗️ Built {}.", model_spec(model));
+            model_fits(model, task);
+        }
+        Err(e) => println!("⚠️ Invalid spec: {}", e),
+    }
+}
+
+/// Sets the one seed behind weight initialization, shuffling and the data
+/// split, and redraws the weights from it.
+fn seed_menu(model: &mut Model, split: &mut SplitConfig) {
+    let seed: u64 = read_or_default("🎲 Enter seed", model.seed());
+    model.reseed(seed);
+    split.seed = seed;
+    println!("🎲 Seed set to {}: split and shuffling follow it.", seed);
+
+    print!("🎲 Redraw the weights from the new seed? This discards trained weights. (y/N): ");
+    io::stdout().flush().unwrap();
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).unwrap();
+    if answer.trim().eq_ignore_ascii_case("y") {
+        let init = choose_initializer();
+        let bias: f32 = read_or_default("Enter bias value", 0.0);
+        model.initialize_weights(init, bias);
+        println!("🎲 Weights drawn with {}, biases set to {}.", init, bias);
+    }
+}
+
+fn read_model_path() -> String {
+    print!("📁 Enter file path [{}]: ", DEFAULT_MODEL_PATH);
+    io::stdout().flush().unwrap();
+    let mut input = String::new();
+    io::stdin().read_line(&mut input).unwrap();
+    let path = input.trim();
+    if path.is_empty() {
+        DEFAULT_MODEL_PATH.to_string()
+    } else {
+        path.to_string()
+    }
+}
+
+fn save_menu(model: &Model) {
+    let path = read_model_path();
+    match save_model(model, Path::new(&path)) {
+        Ok(()) => println!("💾 Model saved to {}.", path),
+        Err(e) => println!("⚠️ Could not save model: {}", e),
+    }
+}
+
+fn load_menu(model: &mut Model, task: Task, split: &mut SplitConfig) {
+    let path = read_model_path();
+    match load_model(Path::new(&path)) {
+        Ok(loaded) => {
+            *model = loaded;
+            split.seed = model.seed();
+            println!("📂 Model loaded from {} (seed {}).", path, model.seed());
+            model_fits(model, task);
+        }
+        Err(e) => println!("⚠️ Could not load model: {}", e),
+    }
+}
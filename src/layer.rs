@@ -1,16 +1,49 @@
-use crate::matrix::Matrix;
+use crate::activation::Activation;
+use crate::init::Initializer;
+use crate::matrix::{Axis, Matrix};
+use rand::Rng;
+
+/// Added to the variance before the square root so constant features do
+/// not divide by zero.
+const NORM_EPSILON: f32 = 1e-5;
+
+/// Weight of the newest batch in batch norm's running statistics.
+const BATCH_NORM_MOMENTUM: f32 = 0.1;
 
 #[derive(Clone)]
 pub enum LayerEnum {
     Dense(DenseLayer),
     Activation(ActivationLayer),
+    Softmax(SoftmaxLayer),
+    Dropout(DropoutLayer),
+    BatchNorm(BatchNormLayer),
+    LayerNorm(LayerNormLayer),
 }
 
 impl LayerEnum {
-    pub fn forward(&mut self, input: &Matrix) -> Matrix {
+    /// Forward pass that caches what the layer's `backward` needs. With
+    /// `training` off, dropout passes its input through unchanged and batch
+    /// norm uses its running statistics; dropout masks are drawn from `rng`.
+    pub fn forward(&mut self, input: &Matrix, training: bool, rng: &mut impl Rng) -> Matrix {
         match self {
             LayerEnum::Dense(layer) => layer.forward(input),
             LayerEnum::Activation(layer) => layer.forward(input),
+            LayerEnum::Softmax(layer) => layer.forward(input),
+            LayerEnum::Dropout(layer) => layer.forward(input, training, rng),
+            LayerEnum::BatchNorm(layer) => layer.forward(input, training),
+            LayerEnum::LayerNorm(layer) => layer.forward(input),
+        }
+    }
+
+    /// Inference-only forward pass that leaves the training caches alone.
+    pub fn predict(&self, input: &Matrix) -> Matrix {
+        match self {
+            LayerEnum::Dense(layer) => layer.predict(input),
+            LayerEnum::Activation(layer) => layer.predict(input),
+            LayerEnum::Softmax(layer) => layer.predict(input),
+            LayerEnum::Dropout(_) => input.clone(),
+            LayerEnum::BatchNorm(layer) => layer.predict(input),
+            LayerEnum::LayerNorm(layer) => layer.predict(input),
         }
     }
 
@@ -18,13 +51,41 @@ impl LayerEnum {
         match self {
             LayerEnum::Dense(layer) => layer.backward(input, grad_output),
             LayerEnum::Activation(layer) => layer.backward(input, grad_output),
+            LayerEnum::Softmax(layer) => layer.backward(input, grad_output),
+            LayerEnum::Dropout(layer) => layer.backward(input, grad_output),
+            LayerEnum::BatchNorm(layer) => layer.backward(input, grad_output),
+            LayerEnum::LayerNorm(layer) => layer.backward(input, grad_output),
+        }
+    }
+
+    /// Trainable parameters paired with their gradient from the last
+    /// backward pass, if any.
+    pub fn params_mut(&mut self) -> Vec<(&mut Matrix, Option<&Matrix>)> {
+        match self {
+            LayerEnum::Dense(layer) => vec![
+                (&mut layer.weights, layer.grad_weights.as_ref()),
+                (&mut layer.biases, layer.grad_biases.as_ref()),
+            ],
+            LayerEnum::BatchNorm(layer) => vec![
+                (&mut layer.gamma, layer.grad_gamma.as_ref()),
+                (&mut layer.beta, layer.grad_beta.as_ref()),
+            ],
+            LayerEnum::LayerNorm(layer) => vec![
+                (&mut layer.gamma, layer.grad_gamma.as_ref()),
+                (&mut layer.beta, layer.grad_beta.as_ref()),
+            ],
+            LayerEnum::Activation(_) | LayerEnum::Softmax(_) | LayerEnum::Dropout(_) => Vec::new(),
         }
     }
 
-    pub fn update(&mut self, learning_rate: f32) {
+    /// Gradient slots in the order of `params_mut`, for storing gradients
+    /// computed elsewhere, e.g. reduced from several threads.
+    pub fn grads_mut(&mut self) -> Vec<&mut Option<Matrix>> {
         match self {
-            LayerEnum::Dense(layer) => layer.update(learning_rate),
-            LayerEnum::Activation(layer) => layer.update(learning_rate),
+            LayerEnum::Dense(layer) => vec![&mut layer.grad_weights, &mut layer.grad_biases],
+            LayerEnum::BatchNorm(layer) => vec![&mut layer.grad_gamma, &mut layer.grad_beta],
+            LayerEnum::LayerNorm(layer) => vec![&mut layer.grad_gamma, &mut layer.grad_beta],
+            LayerEnum::Activation(_) | LayerEnum::Softmax(_) | LayerEnum::Dropout(_) => Vec::new(),
         }
     }
 
@@ -35,8 +96,34 @@ impl LayerEnum {
                 layer.weights.print("Weights");
                 layer.biases.print("Biases");
             }
-            LayerEnum::Activation(_) => {
-                println!("⚙️ Activation Layer {} (no weights)", index);
+            LayerEnum::Activation(layer) => {
+                println!(
+                    "⚙️ Activation Layer {} ({}, no weights)",
+                    index,
+                    layer.activation()
+                );
+            }
+            LayerEnum::Softmax(_) => {
+                println!("⚙️ Softmax Layer {} (no weights)", index);
+            }
+            LayerEnum::Dropout(layer) => {
+                println!(
+                    "⚙️ Dropout Layer {} (rate {}, no weights)",
+                    index,
+                    layer.rate()
+                );
+            }
+            LayerEnum::BatchNorm(layer) => {
+                println!("📊 BatchNorm Layer {} Parameters:", index);
+                layer.gamma.print("Gamma");
+                layer.beta.print("Beta");
+                layer.running_mean.print("Running mean");
+                layer.running_var.print("Running variance");
+            }
+            LayerEnum::LayerNorm(layer) => {
+                println!("📊 LayerNorm Layer {} Parameters:", index);
+                layer.gamma.print("Gamma");
+                layer.beta.print("Beta");
             }
         }
     }
@@ -46,100 +133,500 @@ impl LayerEnum {
 pub struct DenseLayer {
     pub weights: Matrix,
     pub biases: Matrix,
-    pub last_input: Option<Matrix>,
     pub grad_weights: Option<Matrix>,
     pub grad_biases: Option<Matrix>,
 }
 
 impl DenseLayer {
-    pub fn new(input_size: usize, output_size: usize) -> Self {
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
-        let weights = Matrix::from_vec(
-            (0..input_size)
-                .map(|_| (0..output_size).map(|_| rng.gen_range(-1.0..1.0)).collect())
-                .collect(),
-        );
-
+    /// Draws the weights uniformly from (-1, 1) using `rng`.
+    pub fn new(input_size: usize, output_size: usize, rng: &mut impl Rng) -> Self {
+        let weights = Initializer::Uniform.weights(input_size, output_size, rng);
         let biases = Matrix::new(1, output_size);
+        Self::from_parts(weights, biases)
+    }
+
+    /// Redraws the weights with `init` and sets every bias to `bias`.
+    pub fn initialize(&mut self, init: Initializer, bias: f32, rng: &mut impl Rng) {
+        self.weights = init.weights(self.weights.rows(), self.weights.cols(), rng);
+        self.biases = Matrix::from_vec(vec![vec![bias; self.biases.cols()]]);
+    }
+
+    pub fn from_parts(weights: Matrix, biases: Matrix) -> Self {
         Self {
             weights,
             biases,
-            last_input: None,
             grad_weights: None,
             grad_biases: None,
         }
     }
 
     pub fn forward(&mut self, input: &Matrix) -> Matrix {
-        let dot = input.dot(&self.weights);
-        let output = dot.map_rows(|row| {
-            row.iter()
-                .zip(self.biases.row(0).iter())
-                .map(|(x, b)| x + b)
-                .collect()
-        });
-        Matrix::from_vec(output)
+        self.predict(input)
+    }
+
+    pub fn predict(&self, input: &Matrix) -> Matrix {
+        let mut output = input.dot(&self.weights);
+        output.broadcast_add_assign(&self.biases);
+        output
     }
 
     pub fn backward(&mut self, input: &Matrix, grad_output: &Matrix) -> Matrix {
-        self.last_input = Some(input.clone());
         let grad_w = input.transpose().dot(grad_output);
         let grad_b = grad_output.sum_rows();
         self.grad_weights = Some(grad_w);
         self.grad_biases = Some(grad_b);
         grad_output.dot(&self.weights.transpose())
     }
-
-    pub fn update(&mut self, learning_rate: f32) {
-        if let Some(gw) = &self.grad_weights {
-            self.weights = self.weights.add(&gw.map(|v| -learning_rate * v));
-        }
-        if let Some(gb) = &self.grad_biases {
-            self.biases = self.biases.add(&gb.map(|v| -learning_rate * v));
-        }
-    }
 }
 
 #[derive(Clone)]
 pub struct ActivationLayer {
-    activation_fn: fn(f32) -> f32,
+    activation: Activation,
     last_output: Option<Matrix>,
 }
 
 impl ActivationLayer {
-    pub fn new(activation_fn: fn(f32) -> f32) -> Self {
+    pub fn new(activation: Activation) -> Self {
         Self {
-            activation_fn,
+            activation,
             last_output: None,
         }
     }
 
-    fn activation_derivative(&self, y: f32) -> f32 {
-        y * (1.0 - y)
+    pub fn activation(&self) -> Activation {
+        self.activation
     }
 
     pub fn forward(&mut self, input: &Matrix) -> Matrix {
-        let output = input.map(self.activation_fn);
+        let output = self.predict(input);
         self.last_output = Some(output.clone());
         output
     }
 
+    pub fn predict(&self, input: &Matrix) -> Matrix {
+        input.map(|x| self.activation.apply(x))
+    }
+
+    pub fn backward(&mut self, input: &Matrix, grad_output: &Matrix) -> Matrix {
+        let cached = self.last_output.as_ref().expect("Missing cached output");
+        let mut result = grad_output.clone();
+        for ((dy, &x), &y) in result
+            .as_mut_slice()
+            .iter_mut()
+            .zip(input.as_slice())
+            .zip(cached.as_slice())
+        {
+            *dy *= self.activation.derivative(x, y);
+        }
+        result
+    }
+}
+
+/// Row-wise softmax, turning each row of scores into class probabilities.
+#[derive(Clone, Default)]
+pub struct SoftmaxLayer {
+    last_output: Option<Matrix>,
+}
+
+impl SoftmaxLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn forward(&mut self, input: &Matrix) -> Matrix {
+        let output = self.predict(input);
+        self.last_output = Some(output.clone());
+        output
+    }
+
+    pub fn predict(&self, input: &Matrix) -> Matrix {
+        Matrix::from_vec(input.map_rows(|row| {
+            // Shifting by the row maximum keeps exp() from overflowing.
+            let max = row.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+            let exps: Vec<f32> = row.iter().map(|&x| (x - max).exp()).collect();
+            let sum: f32 = exps.iter().sum();
+            exps.iter().map(|&e| e / sum).collect()
+        }))
+    }
+
     pub fn backward(&mut self, _input: &Matrix, grad_output: &Matrix) -> Matrix {
         let cached = self.last_output.as_ref().expect("Missing cached output");
-        let result = cached
-            .data()
+        let mut result = grad_output.clone();
+        for r in 0..result.rows() {
+            let ys = cached.row(r);
+            let dys = result.row_mut(r);
+            let dot: f32 = ys.iter().zip(dys.iter()).map(|(y, dy)| y * dy).sum();
+            for (dy, &y) in dys.iter_mut().zip(ys) {
+                *dy = y * (*dy - dot);
+            }
+        }
+        result
+    }
+}
+
+/// Zeroes each input with probability `rate` while training and scales the
+/// survivors by `1 / (1 - rate)`, so the expected output equals the input
+/// and nothing needs rescaling at inference time.
+#[derive(Clone)]
+pub struct DropoutLayer {
+    rate: f32,
+    /// The scale applied to each input in the last training forward pass:
+    /// 0 for dropped inputs, `1 / (1 - rate)` for kept ones.
+    mask: Option<Matrix>,
+}
+
+impl DropoutLayer {
+    /// Fails unless `0 <= rate < 1`.
+    pub fn new(rate: f32) -> Result<Self, String> {
+        if !(0.0..1.0).contains(&rate) {
+            return Err(format!("dropout rate {} is not in [0, 1)", rate));
+        }
+        Ok(Self { rate, mask: None })
+    }
+
+    pub fn rate(&self) -> f32 {
+        self.rate
+    }
+
+    pub fn forward(&mut self, input: &Matrix, training: bool, rng: &mut impl Rng) -> Matrix {
+        if !training {
+            self.mask = None;
+            return input.clone();
+        }
+        let keep = 1.0 - self.rate;
+        let mask = Matrix::from_fn(input.rows(), input.cols(), |_, _| {
+            if rng.gen::<f32>() < keep {
+                1.0 / keep
+            } else {
+                0.0
+            }
+        });
+        let output = input.hadamard(&mask);
+        self.mask = Some(mask);
+        output
+    }
+
+    pub fn backward(&mut self, _input: &Matrix, grad_output: &Matrix) -> Matrix {
+        match &self.mask {
+            Some(mask) => grad_output.hadamard(mask),
+            None => grad_output.clone(),
+        }
+    }
+}
+
+/// What a normalization layer's backward pass needs from its forward pass.
+#[derive(Clone)]
+struct NormCache {
+    /// The standardized input, before gamma and beta are applied.
+    normalized: Matrix,
+    /// `1 / sqrt(var + eps)`, one entry per normalized group.
+    inv_std: Matrix,
+    /// Whether the mean and variance were computed from the input, so the
+    /// gradient has to flow through them too.
+    from_input: bool,
+}
+
+/// Mean and (biased) variance along `axis`.
+fn moments(input: &Matrix, axis: Axis) -> (Matrix, Matrix) {
+    let mean = input.mean_axis(axis);
+    let centered = input.broadcast_sub(&mean);
+    let var = centered.hadamard(&centered).mean_axis(axis);
+    (mean, var)
+}
+
+/// `(input - mean) / sqrt(var + eps)` together with `1 / sqrt(var + eps)`.
+fn standardize(input: &Matrix, mean: &Matrix, var: &Matrix) -> (Matrix, Matrix) {
+    let inv_std = var.map(|v| 1.0 / (v + NORM_EPSILON).sqrt());
+    let normalized = input.broadcast_sub(mean).broadcast_mul(&inv_std);
+    (normalized, inv_std)
+}
+
+/// Gradient of the loss with respect to the input of `standardize`, given
+/// its gradient with respect to the standardized values. When the
+/// statistics were computed from the input along `axis`, this is
+/// `inv_std * (g - mean(g) - x_hat * mean(g * x_hat))`; otherwise they are
+/// constants and it is just `inv_std * g`.
+fn standardize_backward(cache: &NormCache, grad_normalized: &Matrix, axis: Axis) -> Matrix {
+    if !cache.from_input {
+        return grad_normalized.broadcast_mul(&cache.inv_std);
+    }
+    let mean_grad = grad_normalized.mean_axis(axis);
+    let mean_grad_dot = grad_normalized.hadamard(&cache.normalized).mean_axis(axis);
+    grad_normalized
+        .broadcast_sub(&mean_grad)
+        .sub(&cache.normalized.broadcast_mul(&mean_grad_dot))
+        .broadcast_mul(&cache.inv_std)
+}
+
+/// Normalizes each feature over the batch, then scales by `gamma` and
+/// shifts by `beta`. Training passes use the statistics of the batch and
+/// fold them into running averages, which inference uses instead.
+#[derive(Clone)]
+pub struct BatchNormLayer {
+    pub gamma: Matrix,
+    pub beta: Matrix,
+    pub running_mean: Matrix,
+    pub running_var: Matrix,
+    pub grad_gamma: Option<Matrix>,
+    pub grad_beta: Option<Matrix>,
+    cache: Option<NormCache>,
+}
+
+impl BatchNormLayer {
+    /// Starts as the identity: gamma 1, beta 0, running mean 0 and running
+    /// variance 1.
+    pub fn new(size: usize) -> Self {
+        Self::from_parts(
+            Matrix::ones(1, size),
+            Matrix::zeros(1, size),
+            Matrix::zeros(1, size),
+            Matrix::ones(1, size),
+        )
+    }
+
+    pub fn from_parts(
+        gamma: Matrix,
+        beta: Matrix,
+        running_mean: Matrix,
+        running_var: Matrix,
+    ) -> Self {
+        Self {
+            gamma,
+            beta,
+            running_mean,
+            running_var,
+            grad_gamma: None,
+            grad_beta: None,
+            cache: None,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.gamma.cols()
+    }
+
+    /// A batch of a single sample has no spread to normalize by, so it is
+    /// treated as in inference, even while training.
+    pub fn forward(&mut self, input: &Matrix, training: bool) -> Matrix {
+        let from_input = training && input.rows() > 1;
+        let (normalized, inv_std) = if from_input {
+            let (mean, var) = moments(input, Axis::Rows);
+            // The running variance is the unbiased estimate.
+            let n = input.rows() as f32;
+            let m = BATCH_NORM_MOMENTUM;
+            self.running_mean.scale_assign(1.0 - m);
+            self.running_mean.add_assign(&mean.scale(m));
+            self.running_var.scale_assign(1.0 - m);
+            self.running_var.add_assign(&var.scale(m * n / (n - 1.0)));
+            standardize(input, &mean, &var)
+        } else {
+            standardize(input, &self.running_mean, &self.running_var)
+        };
+        let output = self.scale_and_shift(&normalized);
+        self.cache = Some(NormCache {
+            normalized,
+            inv_std,
+            from_input,
+        });
+        output
+    }
+
+    pub fn predict(&self, input: &Matrix) -> Matrix {
+        let (normalized, _) = standardize(input, &self.running_mean, &self.running_var);
+        self.scale_and_shift(&normalized)
+    }
+
+    fn scale_and_shift(&self, normalized: &Matrix) -> Matrix {
+        let mut output = normalized.broadcast_mul(&self.gamma);
+        output.broadcast_add_assign(&self.beta);
+        output
+    }
+
+    pub fn backward(&mut self, _input: &Matrix, grad_output: &Matrix) -> Matrix {
+        let cache = self.cache.as_ref().expect("Missing cached output");
+        self.grad_gamma = Some(grad_output.hadamard(&cache.normalized).sum_rows());
+        self.grad_beta = Some(grad_output.sum_rows());
+        let grad_normalized = grad_output.broadcast_mul(&self.gamma);
+        standardize_backward(cache, &grad_normalized, Axis::Rows)
+    }
+}
+
+/// Normalizes each sample over its features, then scales by `gamma` and
+/// shifts by `beta`. Unlike batch norm it behaves the same in training and
+/// inference.
+#[derive(Clone)]
+pub struct LayerNormLayer {
+    pub gamma: Matrix,
+    pub beta: Matrix,
+    pub grad_gamma: Option<Matrix>,
+    pub grad_beta: Option<Matrix>,
+    cache: Option<NormCache>,
+}
+
+impl LayerNormLayer {
+    /// Starts with gamma 1 and beta 0.
+    pub fn new(size: usize) -> Self {
+        Self::from_parts(Matrix::ones(1, size), Matrix::zeros(1, size))
+    }
+
+    pub fn from_parts(gamma: Matrix, beta: Matrix) -> Self {
+        Self {
+            gamma,
+            beta,
+            grad_gamma: None,
+            grad_beta: None,
+            cache: None,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.gamma.cols()
+    }
+
+    pub fn forward(&mut self, input: &Matrix) -> Matrix {
+        let (mean, var) = moments(input, Axis::Cols);
+        let (normalized, inv_std) = standardize(input, &mean, &var);
+        let output = self.scale_and_shift(&normalized);
+        self.cache = Some(NormCache {
+            normalized,
+            inv_std,
+            from_input: true,
+        });
+        output
+    }
+
+    pub fn predict(&self, input: &Matrix) -> Matrix {
+        let (mean, var) = moments(input, Axis::Cols);
+        let (normalized, _) = standardize(input, &mean, &var);
+        self.scale_and_shift(&normalized)
+    }
+
+    fn scale_and_shift(&self, normalized: &Matrix) -> Matrix {
+        let mut output = normalized.broadcast_mul(&self.gamma);
+        output.broadcast_add_assign(&self.beta);
+        output
+    }
+
+    pub fn backward(&mut self, _input: &Matrix, grad_output: &Matrix) -> Matrix {
+        let cache = self.cache.as_ref().expect("Missing cached output");
+        self.grad_gamma = Some(grad_output.hadamard(&cache.normalized).sum_rows());
+        self.grad_beta = Some(grad_output.sum_rows());
+        let grad_normalized = grad_output.broadcast_mul(&self.gamma);
+        standardize_backward(cache, &grad_normalized, Axis::Cols)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dropout_masks_and_scales() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut layer = DropoutLayer::new(0.25).unwrap();
+        let input = Matrix::ones(200, 50);
+        let output = layer.forward(&input, true, &mut rng);
+        let kept = output.as_slice().iter().filter(|&&x| x > 0.0).count();
+        assert!((kept as f32 / 10_000.0 - 0.75).abs() < 0.02);
+        assert!(output
+            .as_slice()
             .iter()
-            .zip(grad_output.data().iter())
-            .map(|(a, b)| {
-                a.iter()
-                    .zip(b.iter())
-                    .map(|(&y, &dy)| dy * self.activation_derivative(y))
-                    .collect()
-            })
-            .collect();
-        Matrix::from_vec(result)
-    }
-
-    pub fn update(&mut self, _learning_rate: f32) {}
+            .all(|&x| x == 0.0 || (x - 1.0 / 0.75).abs() < 1e-6));
+        assert!((output.mean() - 1.0).abs() < 0.03);
+
+        // The gradient flows only through the kept inputs, scaled alike.
+        let grad = layer.backward(&input, &Matrix::filled(200, 50, 2.0));
+        assert_eq!(grad.as_slice(), output.scale(2.0).as_slice());
+
+        let eval = layer.forward(&input, false, &mut rng);
+        assert_eq!(eval.as_slice(), input.as_slice());
+        assert_eq!(layer.backward(&input, &input).as_slice(), input.as_slice());
+        assert!(DropoutLayer::new(1.0).is_err());
+        assert!(DropoutLayer::new(-0.1).is_err());
+    }
+
+    #[test]
+    fn test_batch_norm_normalizes_features() {
+        let mut layer = BatchNormLayer::new(2);
+        let input = Matrix::from_vec(vec![
+            vec![1.0, 10.0],
+            vec![2.0, 20.0],
+            vec![3.0, 30.0],
+            vec![6.0, 40.0],
+        ]);
+        let output = layer.forward(&input, true);
+        let (mean, var) = moments(&output, Axis::Rows);
+        for c in 0..2 {
+            assert!(mean.get(0, c).abs() < 1e-5);
+            assert!((var.get(0, c) - 1.0).abs() < 1e-3);
+        }
+        // One step of momentum from mean 0 and variance 1.
+        assert!((layer.running_mean.get(0, 0) - 0.1 * 3.0).abs() < 1e-6);
+        assert!((layer.running_var.get(0, 0) - (0.9 + 0.1 * 14.0 / 3.0)).abs() < 1e-5);
+
+        // Inference and single-sample batches use the running statistics.
+        let single = input.slice_rows(0..1);
+        let expected = layer.predict(&single);
+        let actual = layer.forward(&single, true);
+        assert_eq!(actual.as_slice(), expected.as_slice());
+        let expected = (1.0 - 0.3) / (0.9f32 + 0.1 * 14.0 / 3.0 + NORM_EPSILON).sqrt();
+        assert!((actual.get(0, 0) - expected).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_layer_norm_normalizes_samples() {
+        let mut layer = LayerNormLayer::from_parts(
+            Matrix::from_vec(vec![vec![2.0, 2.0, 2.0]]),
+            Matrix::from_vec(vec![vec![1.0, 1.0, 1.0]]),
+        );
+        let input = Matrix::from_vec(vec![vec![1.0, 2.0, 3.0], vec![-5.0, 0.0, 5.0]]);
+        let output = layer.forward(&input);
+        assert_eq!(output.as_slice(), layer.predict(&input).as_slice());
+        for r in 0..2 {
+            let row = output.row(r);
+            assert!((row.iter().sum::<f32>() / 3.0 - 1.0).abs() < 1e-5);
+            assert!((row[2] - 1.0 - 2.0 * 1.5f32.sqrt()).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_softmax_rows_sum_to_one() {
+        let mut layer = SoftmaxLayer::new();
+        let input = Matrix::from_vec(vec![vec![1.0, 2.0, 3.0], vec![1000.0, 1000.0, -1000.0]]);
+        let output = layer.forward(&input);
+        for r in 0..2 {
+            let sum: f32 = output.row(r).iter().sum();
+            assert!((sum - 1.0).abs() < 1e-6);
+        }
+        assert!((output.get(1, 0) - 0.5).abs() < 1e-6);
+        assert!(output.get(0, 2) > output.get(0, 1));
+    }
+
+    #[test]
+    fn test_softmax_backward_matches_finite_differences() {
+        let input = Matrix::from_vec(vec![vec![0.2, -0.4, 1.1]]);
+        // Weighting the outputs makes the scalar loss depend on every entry.
+        let weights = [0.3, -1.2, 0.7];
+        let weighted_sum = |m: &Matrix| (0..3).map(|j| weights[j] * m.get(0, j)).sum::<f32>();
+
+        let mut layer = SoftmaxLayer::new();
+        layer.forward(&input);
+        let grad_output = Matrix::from_vec(vec![weights.to_vec()]);
+        let grad = layer.backward(&input, &grad_output);
+
+        let h = 1e-3;
+        for j in 0..3 {
+            let mut plus = input.clone();
+            plus.set(0, j, input.get(0, j) + h);
+            let mut minus = input.clone();
+            minus.set(0, j, input.get(0, j) - h);
+            let numeric = (weighted_sum(&layer.predict(&plus))
+                - weighted_sum(&layer.predict(&minus)))
+                / (2.0 * h);
+            assert!((numeric - grad.get(0, j)).abs() < 1e-3);
+        }
+    }
 }
@@ -1,8 +1,17 @@
-use crate::matrix::Matrix;
+use crate::activation::Activation;
+use crate::layer::LayerEnum;
+use crate::matrix::{Matrix, MatrixError};
+
+/// Probabilities are clamped to this before taking a logarithm.
+const EPSILON: f32 = 1e-7;
+
+/// Focusing parameter of focal loss unless one is given.
+pub const DEFAULT_FOCAL_GAMMA: f32 = 2.0;
 
 pub fn mean_squared_error(predicted: &Matrix, target: &Matrix) -> f32 {
-    assert_eq!(predicted.rows(), target.rows());
-    assert_eq!(predicted.cols(), target.cols());
+    predicted
+        .check_same_shape(target, "mean_squared_error")
+        .unwrap_or_else(|e| panic!("{}", e));
 
     let total: f32 = (0..predicted.rows())
         .map(|i| {
@@ -18,6 +27,314 @@ pub fn mean_squared_error(predicted: &Matrix, target: &Matrix) -> f32 {
     total / (predicted.rows() * predicted.cols()) as f32
 }
 
+/// Mean over rows of `-sum(target * ln(predicted))`, for one-hot targets and
+/// rows of class probabilities.
+pub fn categorical_cross_entropy(predicted: &Matrix, target: &Matrix) -> f32 {
+    predicted
+        .check_same_shape(target, "categorical_cross_entropy")
+        .unwrap_or_else(|e| panic!("{}", e));
+
+    let total: f32 = (0..predicted.rows())
+        .map(|i| {
+            (0..predicted.cols())
+                .map(|j| -target.get(i, j) * predicted.get(i, j).max(EPSILON).ln())
+                .sum::<f32>()
+        })
+        .sum();
+
+    total / predicted.rows() as f32
+}
+
+/// Per-class multipliers for the binary losses, so the rare positive class
+/// (primes) is not drowned out by the negatives.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClassWeights {
+    pub negative: f32,
+    pub positive: f32,
+}
+
+impl ClassWeights {
+    pub const UNIFORM: ClassWeights = ClassWeights {
+        negative: 1.0,
+        positive: 1.0,
+    };
+
+    /// Fails unless both weights are positive and finite.
+    pub fn new(negative: f32, positive: f32) -> Result<ClassWeights, String> {
+        for (name, weight) in [("negative", negative), ("positive", positive)] {
+            if !(weight > 0.0 && weight.is_finite()) {
+                return Err(format!("{} class weight {} must be positive", name, weight));
+            }
+        }
+        Ok(ClassWeights { negative, positive })
+    }
+
+    /// Weights inversely proportional to how often each label occurs, so
+    /// both classes contribute equally to the loss.
+    pub fn balanced(labels: &[u8]) -> ClassWeights {
+        let positives = labels.iter().filter(|&&y| y == 1).count().max(1) as f32;
+        let negatives = (labels.len() as f32 - positives).max(1.0);
+        let total = labels.len() as f32;
+        ClassWeights {
+            negative: total / (2.0 * negatives),
+            positive: total / (2.0 * positives),
+        }
+    }
+}
+
+/// Applies `f(predicted, target)` to every element and averages the result.
+fn elementwise_mean<F>(predicted: &Matrix, target: &Matrix, f: F) -> f32
+where
+    F: Fn(f32, f32) -> f32,
+{
+    predicted
+        .check_same_shape(target, "loss")
+        .unwrap_or_else(|e| panic!("{}", e));
+
+    let total: f32 = predicted
+        .as_slice()
+        .iter()
+        .zip(target.as_slice())
+        .map(|(&p, &t)| f(p, t))
+        .sum();
+    total / (predicted.rows() * predicted.cols()) as f32
+}
+
+/// Builds the matrix of `f(predicted, target) / n` for every element.
+fn elementwise_gradient<F>(predicted: &Matrix, target: &Matrix, f: F) -> Matrix
+where
+    F: Fn(f32, f32) -> f32,
+{
+    let n = (predicted.rows() * predicted.cols()) as f32;
+    let mut gradient = predicted.clone();
+    for (g, &t) in gradient.as_mut_slice().iter_mut().zip(target.as_slice()) {
+        *g = f(*g, t) / n;
+    }
+    gradient
+}
+
+/// Weighted binary cross-entropy of probability `p` against target `t`.
+fn bce(p: f32, t: f32, weights: ClassWeights) -> f32 {
+    let p = p.clamp(EPSILON, 1.0 - EPSILON);
+    -(weights.positive * t * p.ln() + weights.negative * (1.0 - t) * (1.0 - p).ln())
+}
+
+/// Weighted binary cross-entropy computed from the logit `z`, using
+/// `ln(sigmoid(z)) = -softplus(-z)` so no probability is ever rounded to 0 or 1.
+fn bce_from_logit(z: f32, t: f32, weights: ClassWeights) -> f32 {
+    let softplus = Activation::Softplus;
+    weights.positive * t * softplus.apply(-z) + weights.negative * (1.0 - t) * softplus.apply(z)
+}
+
+/// Gradient of `bce` with respect to the logit behind `p`. This is also the
+/// gradient of `bce_from_logit`.
+fn bce_logit_gradient(p: f32, t: f32, weights: ClassWeights) -> f32 {
+    weights.negative * (1.0 - t) * p - weights.positive * t * (1.0 - p)
+}
+
+/// Focal loss: binary cross-entropy scaled by `(1 - p_t)^gamma`, which
+/// shrinks the loss of samples that are already classified confidently.
+fn focal(p: f32, t: f32, gamma: f32, weights: ClassWeights) -> f32 {
+    let p = p.clamp(EPSILON, 1.0 - EPSILON);
+    -(weights.positive * t * (1.0 - p).powf(gamma) * p.ln()
+        + weights.negative * (1.0 - t) * p.powf(gamma) * (1.0 - p).ln())
+}
+
+fn focal_gradient(p: f32, t: f32, gamma: f32, weights: ClassWeights) -> f32 {
+    let p = p.clamp(EPSILON, 1.0 - EPSILON);
+    let q = 1.0 - p;
+    weights.positive * t * (gamma * q.powf(gamma - 1.0) * p.ln() - q.powf(gamma) / p)
+        + weights.negative * (1.0 - t) * (p.powf(gamma) / q - gamma * p.powf(gamma - 1.0) * q.ln())
+}
+
+/// Focal loss gradient with respect to the logit, i.e. `focal_gradient`
+/// multiplied by the sigmoid derivative `p * (1 - p)`.
+fn focal_logit_gradient(p: f32, t: f32, gamma: f32, weights: ClassWeights) -> f32 {
+    let p = p.clamp(EPSILON, 1.0 - EPSILON);
+    let q = 1.0 - p;
+    weights.positive * t * (gamma * q.powf(gamma) * p * p.ln() - q.powf(gamma + 1.0))
+        + weights.negative * (1.0 - t) * (p.powf(gamma + 1.0) - gamma * p.powf(gamma) * q * q.ln())
+}
+
+/// Loss function minimized by `train_step`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Loss {
+    MeanSquaredError,
+    CategoricalCrossEntropy,
+    /// Binary cross-entropy. With `from_logits` the model output is taken to
+    /// be the raw score before a sigmoid rather than a probability.
+    BinaryCrossEntropy {
+        weights: ClassWeights,
+        from_logits: bool,
+    },
+    Focal {
+        gamma: f32,
+        weights: ClassWeights,
+    },
+}
+
+impl Loss {
+    /// Focal loss with focusing parameter `gamma`, which must be finite and
+    /// not negative: below 0 the `(1 - p)^gamma` factor blows up near 0.
+    pub fn focal(gamma: f32, weights: ClassWeights) -> Result<Loss, String> {
+        if !gamma.is_finite() || gamma < 0.0 {
+            return Err(format!("focal gamma {} must be 0 or more", gamma));
+        }
+        Ok(Loss::Focal { gamma, weights })
+    }
+
+    /// Checks that a model ending in `last` produces what this loss reads.
+    /// Cross-entropy needs a final softmax, since `-ln(p)` keeps rewarding
+    /// outputs above 1, and `bce` and focal loss a final sigmoid, since they
+    /// clamp anything outside (0, 1). `bce_logits` needs raw scores, i.e. a
+    /// final dense layer or identity activation rather than a sigmoid.
+    pub fn check_output(&self, last: Option<&LayerEnum>) -> Result<(), String> {
+        match self {
+            Loss::CategoricalCrossEntropy => match last {
+                Some(LayerEnum::Softmax(_)) => Ok(()),
+                _ => Err(format!(
+                    "{} needs a model ending in a softmax layer",
+                    self.name()
+                )),
+            },
+            Loss::BinaryCrossEntropy {
+                from_logits: true, ..
+            } => match last {
+                Some(LayerEnum::Dense(_)) => Ok(()),
+                Some(LayerEnum::Activation(a)) if a.activation() == Activation::Identity => Ok(()),
+                _ => Err(format!(
+                    "{} needs a model ending in a plain dense layer; use bce for a sigmoid output",
+                    self.name()
+                )),
+            },
+            Loss::BinaryCrossEntropy { .. } | Loss::Focal { .. } => match last {
+                Some(LayerEnum::Activation(a)) if a.activation() == Activation::Sigmoid => Ok(()),
+                _ => Err(format!(
+                    "{} needs a model ending in a sigmoid; use bce_logits for a plain dense output",
+                    self.name()
+                )),
+            },
+            Loss::MeanSquaredError => Ok(()),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Loss::MeanSquaredError => "mse",
+            Loss::CategoricalCrossEntropy => "cross_entropy",
+            Loss::BinaryCrossEntropy {
+                from_logits: false, ..
+            } => "bce",
+            Loss::BinaryCrossEntropy {
+                from_logits: true, ..
+            } => "bce_logits",
+            Loss::Focal { .. } => "focal",
+        }
+    }
+
+    pub fn compute(&self, predicted: &Matrix, target: &Matrix) -> f32 {
+        self.try_compute(predicted, target)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// `compute` that reports mismatched shapes instead of panicking.
+    pub fn try_compute(&self, predicted: &Matrix, target: &Matrix) -> Result<f32, MatrixError> {
+        predicted.check_same_shape(target, self.name())?;
+        Ok(match *self {
+            Loss::MeanSquaredError => mean_squared_error(predicted, target),
+            Loss::CategoricalCrossEntropy => categorical_cross_entropy(predicted, target),
+            Loss::BinaryCrossEntropy {
+                weights,
+                from_logits: false,
+            } => elementwise_mean(predicted, target, |p, t| bce(p, t, weights)),
+            Loss::BinaryCrossEntropy {
+                weights,
+                from_logits: true,
+            } => elementwise_mean(predicted, target, |z, t| bce_from_logit(z, t, weights)),
+            Loss::Focal { gamma, weights } => {
+                elementwise_mean(predicted, target, |p, t| focal(p, t, gamma, weights))
+            }
+        })
+    }
+
+    /// Gradient of `compute` with respect to `predicted`.
+    pub fn gradient(&self, predicted: &Matrix, target: &Matrix) -> Matrix {
+        match *self {
+            Loss::MeanSquaredError => {
+                let mut gradient = predicted.sub(target);
+                gradient.scale_assign(2.0 / (predicted.rows() * predicted.cols()) as f32);
+                gradient
+            }
+            Loss::CategoricalCrossEntropy => {
+                let scale = -1.0 / predicted.rows() as f32;
+                let data = (0..predicted.rows())
+                    .map(|i| {
+                        (0..predicted.cols())
+                            .map(|j| scale * target.get(i, j) / predicted.get(i, j).max(EPSILON))
+                            .collect()
+                    })
+                    .collect();
+                Matrix::from_vec(data)
+            }
+            Loss::BinaryCrossEntropy {
+                weights,
+                from_logits: false,
+            } => elementwise_gradient(predicted, target, |p, t| {
+                let p = p.clamp(EPSILON, 1.0 - EPSILON);
+                bce_logit_gradient(p, t, weights) / (p * (1.0 - p))
+            }),
+            Loss::BinaryCrossEntropy {
+                weights,
+                from_logits: true,
+            } => elementwise_gradient(predicted, target, |z, t| {
+                bce_logit_gradient(Activation::Sigmoid.apply(z), t, weights)
+            }),
+            Loss::Focal { gamma, weights } => elementwise_gradient(predicted, target, |p, t| {
+                focal_gradient(p, t, gamma, weights)
+            }),
+        }
+    }
+
+    /// When the model ends in `last`, returns the gradient with respect to
+    /// that layer's input directly, so its own backward pass can be skipped.
+    /// Softmax followed by cross-entropy collapses to `(p - t) / n`, and a
+    /// sigmoid followed by a binary loss has a similar closed form. Both
+    /// avoid dividing by tiny probabilities.
+    pub fn fused_gradient(
+        &self,
+        last: &LayerEnum,
+        predicted: &Matrix,
+        target: &Matrix,
+    ) -> Option<Matrix> {
+        match (self, last) {
+            (Loss::CategoricalCrossEntropy, LayerEnum::Softmax(_)) => {
+                let mut gradient = predicted.sub(target);
+                gradient.scale_assign(1.0 / predicted.rows() as f32);
+                Some(gradient)
+            }
+            (
+                Loss::BinaryCrossEntropy {
+                    weights,
+                    from_logits: false,
+                },
+                LayerEnum::Activation(a),
+            ) if a.activation() == Activation::Sigmoid => {
+                Some(elementwise_gradient(predicted, target, |p, t| {
+                    bce_logit_gradient(p, t, *weights)
+                }))
+            }
+            (Loss::Focal { gamma, weights }, LayerEnum::Activation(a))
+                if a.activation() == Activation::Sigmoid =>
+            {
+                Some(elementwise_gradient(predicted, target, |p, t| {
+                    focal_logit_gradient(p, t, *gamma, *weights)
+                }))
+            }
+            _ => None,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -30,4 +347,199 @@ mod tests {
         let loss = mean_squared_error(&predicted, &target);
         assert!((loss - 0.01).abs() < 1e-5);
     }
+
+    #[test]
+    fn test_categorical_cross_entropy() {
+        let predicted = Matrix::from_vec(vec![vec![0.7, 0.2, 0.1], vec![0.25, 0.5, 0.25]]);
+        let target = Matrix::from_vec(vec![vec![1.0, 0.0, 0.0], vec![0.0, 1.0, 0.0]]);
+        let loss = categorical_cross_entropy(&predicted, &target);
+        let expected = -(0.7f32.ln() + 0.5f32.ln()) / 2.0;
+        assert!((loss - expected).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_loss_gradients_match_finite_differences() {
+        let predicted = Matrix::from_vec(vec![vec![0.6, 0.3, 0.1], vec![0.2, 0.5, 0.3]]);
+        let target = Matrix::from_vec(vec![vec![0.0, 1.0, 0.0], vec![0.0, 0.0, 1.0]]);
+        let h = 1e-3;
+        let weights = ClassWeights {
+            negative: 0.6,
+            positive: 2.5,
+        };
+        for loss in [
+            Loss::MeanSquaredError,
+            Loss::CategoricalCrossEntropy,
+            Loss::BinaryCrossEntropy {
+                weights,
+                from_logits: false,
+            },
+            Loss::BinaryCrossEntropy {
+                weights,
+                from_logits: true,
+            },
+            Loss::Focal {
+                gamma: 2.0,
+                weights,
+            },
+        ] {
+            let grad = loss.gradient(&predicted, &target);
+            for i in 0..2 {
+                for j in 0..3 {
+                    let mut plus = predicted.clone();
+                    plus.set(i, j, predicted.get(i, j) + h);
+                    let mut minus = predicted.clone();
+                    minus.set(i, j, predicted.get(i, j) - h);
+                    let numeric =
+                        (loss.compute(&plus, &target) - loss.compute(&minus, &target)) / (2.0 * h);
+                    assert!(
+                        (numeric - grad.get(i, j)).abs() < 1e-2,
+                        "{} at ({}, {})",
+                        loss.name(),
+                        i,
+                        j
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_binary_cross_entropy_and_logits_agree() {
+        let logits = Matrix::from_vec(vec![vec![2.0], vec![-1.0], vec![0.3]]);
+        let probs = logits.map(|z| Activation::Sigmoid.apply(z));
+        let target = Matrix::from_vec(vec![vec![1.0], vec![0.0], vec![0.0]]);
+        let weights = ClassWeights::UNIFORM;
+
+        let bce = Loss::BinaryCrossEntropy {
+            weights,
+            from_logits: false,
+        };
+        let bce_logits = Loss::BinaryCrossEntropy {
+            weights,
+            from_logits: true,
+        };
+        let expected =
+            -((probs.get(0, 0)).ln() + (1.0 - probs.get(1, 0)).ln() + (1.0 - probs.get(2, 0)).ln())
+                / 3.0;
+        assert!((bce.compute(&probs, &target) - expected).abs() < 1e-5);
+        assert!((bce_logits.compute(&logits, &target) - expected).abs() < 1e-5);
+
+        // A huge logit saturates the sigmoid but still gives a finite loss.
+        let extreme = Matrix::from_vec(vec![vec![-200.0]]);
+        let loss = bce_logits.compute(&extreme, &Matrix::from_vec(vec![vec![1.0]]));
+        assert!((loss - 200.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_fused_sigmoid_gradients_match_chain_rule() {
+        use crate::layer::ActivationLayer;
+
+        let sigmoid = LayerEnum::Activation(ActivationLayer::new(Activation::Sigmoid));
+        let logits = Matrix::from_vec(vec![vec![0.8], vec![-1.3]]);
+        let probs = logits.map(|z| Activation::Sigmoid.apply(z));
+        let target = Matrix::from_vec(vec![vec![1.0], vec![0.0]]);
+        let weights = ClassWeights {
+            negative: 0.5,
+            positive: 3.0,
+        };
+
+        for loss in [
+            Loss::BinaryCrossEntropy {
+                weights,
+                from_logits: false,
+            },
+            Loss::Focal {
+                gamma: 2.0,
+                weights,
+            },
+        ] {
+            let fused = loss.fused_gradient(&sigmoid, &probs, &target).unwrap();
+            let grad = loss.gradient(&probs, &target);
+            for i in 0..2 {
+                let p = probs.get(i, 0);
+                let chained = grad.get(i, 0) * p * (1.0 - p);
+                assert!((fused.get(i, 0) - chained).abs() < 1e-5, "{}", loss.name());
+            }
+        }
+    }
+
+    #[test]
+    fn test_balanced_class_weights() {
+        let labels = [1, 0, 0, 0];
+        let weights = ClassWeights::balanced(&labels);
+        assert!((weights.positive - 2.0).abs() < 1e-6);
+        assert!((weights.negative - 4.0 / 6.0).abs() < 1e-6);
+        assert!(ClassWeights::new(0.5, 2.0).is_ok());
+        assert!(ClassWeights::new(0.0, 2.0).is_err());
+        assert!(ClassWeights::new(1.0, -1.0).is_err());
+        assert!(ClassWeights::new(f32::NAN, 1.0).is_err());
+    }
+
+    #[test]
+    fn test_logits_need_a_raw_output() {
+        use crate::layer::{ActivationLayer, SoftmaxLayer};
+        use crate::spec::parse_spec;
+
+        let logits = Loss::BinaryCrossEntropy {
+            weights: ClassWeights::UNIFORM,
+            from_logits: true,
+        };
+        let sigmoid = LayerEnum::Activation(ActivationLayer::new(Activation::Sigmoid));
+        let identity = LayerEnum::Activation(ActivationLayer::new(Activation::Identity));
+        assert!(logits.check_output(Some(&sigmoid)).is_err());
+        assert!(logits.check_output(Some(&identity)).is_ok());
+        assert!(logits
+            .check_output(Some(&LayerEnum::Softmax(SoftmaxLayer::new())))
+            .is_err());
+        assert!(Loss::MeanSquaredError.check_output(Some(&sigmoid)).is_ok());
+
+        // Cross-entropy reads probabilities from a softmax, as in 10-d3-softmax
+        // but not 10-d3.
+        let softmax = LayerEnum::Softmax(SoftmaxLayer::new());
+        let dense = parse_spec("10-d3", 1).unwrap().layers()[0].clone();
+        let cross_entropy = Loss::CategoricalCrossEntropy;
+        assert!(cross_entropy.check_output(Some(&softmax)).is_ok());
+        assert!(cross_entropy.check_output(Some(&dense)).is_err());
+        assert!(cross_entropy.check_output(Some(&sigmoid)).is_err());
+    }
+
+    #[test]
+    fn test_focal_gamma_is_checked() {
+        let weights = ClassWeights::UNIFORM;
+        assert_eq!(
+            Loss::focal(0.0, weights),
+            Ok(Loss::Focal {
+                gamma: 0.0,
+                weights
+            })
+        );
+        for gamma in [-0.5, f32::NAN, f32::INFINITY] {
+            assert!(Loss::focal(gamma, weights).is_err(), "{}", gamma);
+        }
+    }
+
+    #[test]
+    fn test_probability_losses_need_a_sigmoid() {
+        use crate::layer::ActivationLayer;
+
+        let sigmoid = LayerEnum::Activation(ActivationLayer::new(Activation::Sigmoid));
+        let relu = LayerEnum::Activation(ActivationLayer::new(Activation::Relu));
+        let identity = LayerEnum::Activation(ActivationLayer::new(Activation::Identity));
+        let bce = Loss::BinaryCrossEntropy {
+            weights: ClassWeights::UNIFORM,
+            from_logits: false,
+        };
+        let focal = Loss::Focal {
+            gamma: 2.0,
+            weights: ClassWeights::UNIFORM,
+        };
+        for loss in [bce, focal] {
+            assert!(loss.check_output(Some(&sigmoid)).is_ok());
+            assert!(loss.check_output(Some(&relu)).is_err());
+            assert!(loss.check_output(Some(&identity)).is_err());
+            assert!(loss.check_output(None).is_err());
+        }
+        let err = bce.check_output(Some(&relu)).unwrap_err();
+        assert!(err.contains("use bce_logits"), "{}", err);
+    }
 }
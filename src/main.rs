@@ -1,12 +1,28 @@
 mod activation;
+mod bench;
+mod cli;
 mod dataset;
+mod evaluation;
+mod gradcheck;
+mod init;
 mod interface;
 mod layer;
 mod loss;
 mod matrix;
 mod model;
+mod optimizer;
+mod parallel;
+mod regularization;
+mod schedule;
+mod spec;
+mod storage;
 mod train;
 
 fn main() {
-    interface::run_ui();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() {
+        interface::run_ui();
+    } else {
+        std::process::exit(cli::run(&args));
+    }
 }
@@ -1,8 +1,92 @@
+use std::fmt;
+use std::ops::{self, Range};
+
+/// Why a matrix could not be built or an operation could not run.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatrixError {
+    /// `from_vec` was given no rows.
+    Empty,
+    /// A row of `from_vec` input is longer or shorter than the first row.
+    RaggedRow {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// The operands of `op` have incompatible shapes, given as (rows, cols).
+    ShapeMismatch {
+        op: &'static str,
+        left: (usize, usize),
+        right: (usize, usize),
+    },
+    /// An element index lies outside a matrix of shape (rows, cols).
+    OutOfBounds {
+        row: usize,
+        col: usize,
+        shape: (usize, usize),
+    },
+    /// The rows `start..end` are not a valid range of a matrix with `rows` rows.
+    RowRange {
+        start: usize,
+        end: usize,
+        rows: usize,
+    },
+}
+
+impl fmt::Display for MatrixError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatrixError::Empty => write!(f, "matrix must have at least one row"),
+            MatrixError::RaggedRow {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "row {} has {} columns but the first row has {}",
+                row, found, expected
+            ),
+            MatrixError::ShapeMismatch { op, left, right } => write!(
+                f,
+                "{}: shapes {}x{} and {}x{} do not fit",
+                op, left.0, left.1, right.0, right.1
+            ),
+            MatrixError::OutOfBounds { row, col, shape } => write!(
+                f,
+                "index ({}, {}) is outside a {}x{} matrix",
+                row, col, shape.0, shape.1
+            ),
+            MatrixError::RowRange { start, end, rows } => write!(
+                f,
+                "rows {}..{} are outside a matrix with {} rows",
+                start, end, rows
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MatrixError {}
+
+/// Which way a reduction such as `sum_axis` runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Axis {
+    /// Across the rows, i.e. down each column: the result is 1 x cols.
+    Rows,
+    /// Across the columns, i.e. along each row: the result is rows x 1.
+    Cols,
+}
+
+/// Edge length of the square tiles `dot` works through, chosen so a tile of
+/// each operand stays in cache.
+const BLOCK: usize = 64;
+
+/// A dense `rows` x `cols` matrix stored in one row-major buffer: element
+/// `(r, c)` lives at `data[r * cols + c]`, so each row is a contiguous slice
+/// and the row stride is `cols`.
 #[derive(Debug, Clone)]
 pub struct Matrix {
     rows: usize,
     cols: usize,
-    data: Vec<Vec<f32>>,
+    data: Vec<f32>,
 }
 
 impl Matrix {
@@ -10,12 +94,53 @@ impl Matrix {
         Matrix {
             rows,
             cols,
-            data: vec![vec![0.0; cols]; rows],
+            data: vec![0.0; rows * cols],
+        }
+    }
+
+    pub fn zeros(rows: usize, cols: usize) -> Self {
+        Self::new(rows, cols)
+    }
+
+    pub fn ones(rows: usize, cols: usize) -> Self {
+        Self::filled(rows, cols, 1.0)
+    }
+
+    /// A matrix with every element set to `value`.
+    pub fn filled(rows: usize, cols: usize, value: f32) -> Self {
+        Matrix {
+            rows,
+            cols,
+            data: vec![value; rows * cols],
         }
     }
 
+    /// The `n` x `n` identity matrix.
+    pub fn identity(n: usize) -> Self {
+        Self::from_fn(n, n, |r, c| if r == c { 1.0 } else { 0.0 })
+    }
+
+    /// Builds a matrix whose element `(r, c)` is `f(r, c)`, filled in
+    /// row-major order.
+    pub fn from_fn<F>(rows: usize, cols: usize, mut f: F) -> Self
+    where
+        F: FnMut(usize, usize) -> f32,
+    {
+        let mut data = Vec::with_capacity(rows * cols);
+        for r in 0..rows {
+            for c in 0..cols {
+                data.push(f(r, c));
+            }
+        }
+        Matrix { rows, cols, data }
+    }
+
     pub fn row(&self, index: usize) -> &[f32] {
-        &self.data[index]
+        &self.data[index * self.cols..(index + 1) * self.cols]
+    }
+
+    pub fn row_mut(&mut self, index: usize) -> &mut [f32] {
+        &mut self.data[index * self.cols..(index + 1) * self.cols]
     }
 
     pub fn rows(&self) -> usize {
@@ -26,103 +151,633 @@ impl Matrix {
         self.cols
     }
 
+    pub fn shape(&self) -> (usize, usize) {
+        (self.rows, self.cols)
+    }
+
     pub fn get(&self, row: usize, col: usize) -> f32 {
-        self.data[row][col]
+        self.data[row * self.cols + col]
     }
 
     pub fn set(&mut self, row: usize, col: usize, value: f32) {
-        self.data[row][col] = value;
+        self.data[row * self.cols + col] = value;
+    }
+
+    /// `get` that returns `None` instead of panicking outside the matrix.
+    pub fn checked_get(&self, row: usize, col: usize) -> Option<f32> {
+        if row < self.rows && col < self.cols {
+            Some(self.get(row, col))
+        } else {
+            None
+        }
+    }
+
+    /// `set` that reports an index outside the matrix instead of panicking.
+    pub fn try_set(&mut self, row: usize, col: usize, value: f32) -> Result<(), MatrixError> {
+        if row < self.rows && col < self.cols {
+            self.set(row, col, value);
+            Ok(())
+        } else {
+            Err(MatrixError::OutOfBounds {
+                row,
+                col,
+                shape: self.shape(),
+            })
+        }
     }
 
     pub fn from_vec(data: Vec<Vec<f32>>) -> Self {
+        Self::try_from_vec(data).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Builds a matrix from rows, which must be non-empty and equally long.
+    pub fn try_from_vec(data: Vec<Vec<f32>>) -> Result<Self, MatrixError> {
         let rows = data.len();
-        assert!(rows > 0, "Matrix must have at least one row");
-        let cols = data[0].len();
-        assert!(
-            data.iter().all(|r| r.len() == cols),
-            "All rows must have the same number of columns"
-        );
+        let cols = data.first().ok_or(MatrixError::Empty)?.len();
+        if let Some((row, r)) = data.iter().enumerate().find(|(_, r)| r.len() != cols) {
+            return Err(MatrixError::RaggedRow {
+                row,
+                expected: cols,
+                found: r.len(),
+            });
+        }
 
-        Matrix { rows, cols, data }
+        Ok(Matrix {
+            rows,
+            cols,
+            data: data.into_iter().flatten().collect(),
+        })
     }
 
-    pub fn add(&self, other: &Matrix) -> Matrix {
-        assert_eq!(self.rows, other.rows, "Row count mismatch");
-        assert_eq!(self.cols, other.cols, "Column count mismatch");
-
-        let data = (0..self.rows)
-            .map(|i| {
-                (0..self.cols)
-                    .map(|j| self.get(i, j) + other.get(i, j))
-                    .collect::<Vec<f32>>()
+    /// Checks that `other` has the same shape, as element-wise `op` needs.
+    pub fn check_same_shape(&self, other: &Matrix, op: &'static str) -> Result<(), MatrixError> {
+        if self.shape() == other.shape() {
+            Ok(())
+        } else {
+            Err(MatrixError::ShapeMismatch {
+                op,
+                left: self.shape(),
+                right: other.shape(),
             })
-            .collect::<Vec<Vec<f32>>>();
+        }
+    }
+
+    /// All elements in row-major order.
+    pub fn as_slice(&self) -> &[f32] {
+        &self.data
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [f32] {
+        &mut self.data
+    }
+
+    pub fn add(&self, other: &Matrix) -> Matrix {
+        self.try_add(other).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_add(&self, other: &Matrix) -> Result<Matrix, MatrixError> {
+        let mut result = self.clone();
+        result.try_add_assign(other)?;
+        Ok(result)
+    }
+
+    /// Adds `other` element by element without allocating.
+    pub fn add_assign(&mut self, other: &Matrix) {
+        self.try_add_assign(other)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_add_assign(&mut self, other: &Matrix) -> Result<(), MatrixError> {
+        self.check_same_shape(other, "add")?;
+        for (a, b) in self.data.iter_mut().zip(&other.data) {
+            *a += b;
+        }
+        Ok(())
+    }
+
+    pub fn sub(&self, other: &Matrix) -> Matrix {
+        self.try_sub(other).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_sub(&self, other: &Matrix) -> Result<Matrix, MatrixError> {
+        self.try_zip_with(other, "sub", |a, b| a - b)
+    }
+
+    /// Subtracts `other` element by element without allocating.
+    pub fn sub_assign(&mut self, other: &Matrix) {
+        self.try_sub_assign(other)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_sub_assign(&mut self, other: &Matrix) -> Result<(), MatrixError> {
+        self.try_zip_assign_with(other, "sub", |a, b| a - b)
+    }
+
+    /// Element-wise (Hadamard) product.
+    pub fn hadamard(&self, other: &Matrix) -> Matrix {
+        self.try_hadamard(other).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_hadamard(&self, other: &Matrix) -> Result<Matrix, MatrixError> {
+        self.try_zip_with(other, "hadamard", |a, b| a * b)
+    }
+
+    /// Multiplies by `other` element by element without allocating.
+    pub fn hadamard_assign(&mut self, other: &Matrix) {
+        self.try_zip_assign_with(other, "hadamard", |a, b| a * b)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Element-wise division.
+    pub fn div(&self, other: &Matrix) -> Matrix {
+        self.try_div(other).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_div(&self, other: &Matrix) -> Result<Matrix, MatrixError> {
+        self.try_zip_with(other, "div", |a, b| a / b)
+    }
+
+    /// Combines every element with the element of `other` at the same
+    /// position. `op` names the operation in the error.
+    pub fn try_zip_with<F>(
+        &self,
+        other: &Matrix,
+        op: &'static str,
+        f: F,
+    ) -> Result<Matrix, MatrixError>
+    where
+        F: Fn(f32, f32) -> f32,
+    {
+        let mut result = self.clone();
+        result.try_zip_assign_with(other, op, f)?;
+        Ok(result)
+    }
+
+    pub fn try_zip_assign_with<F>(
+        &mut self,
+        other: &Matrix,
+        op: &'static str,
+        f: F,
+    ) -> Result<(), MatrixError>
+    where
+        F: Fn(f32, f32) -> f32,
+    {
+        self.check_same_shape(other, op)?;
+        for (a, &b) in self.data.iter_mut().zip(&other.data) {
+            *a = f(*a, b);
+        }
+        Ok(())
+    }
+
+    /// Multiplies every element by `factor`.
+    pub fn scale(&self, factor: f32) -> Matrix {
+        self.map(|x| x * factor)
+    }
+
+    /// Multiplies every element by `factor` without allocating.
+    pub fn scale_assign(&mut self, factor: f32) {
+        self.map_inplace(|x| x * factor);
+    }
+
+    /// Adds `value` to every element.
+    pub fn add_scalar(&self, value: f32) -> Matrix {
+        self.map(|x| x + value)
+    }
+
+    pub fn add_scalar_assign(&mut self, value: f32) {
+        self.map_inplace(|x| x + value);
+    }
+
+    /// Combines every element with the matching element of `other`, which
+    /// may have the same shape, be a single row (1 x cols) applied to every
+    /// row, a single column (rows x 1) applied to every column, or a 1 x 1
+    /// scalar.
+    pub fn try_broadcast_with<F>(
+        &self,
+        other: &Matrix,
+        op: &'static str,
+        f: F,
+    ) -> Result<Matrix, MatrixError>
+    where
+        F: Fn(f32, f32) -> f32,
+    {
+        let mut result = self.clone();
+        result.try_broadcast_assign_with(other, op, f)?;
+        Ok(result)
+    }
+
+    pub fn try_broadcast_assign_with<F>(
+        &mut self,
+        other: &Matrix,
+        op: &'static str,
+        f: F,
+    ) -> Result<(), MatrixError>
+    where
+        F: Fn(f32, f32) -> f32,
+    {
+        let fits = |mine: usize, theirs: usize| theirs == mine || theirs == 1;
+        if !fits(self.rows, other.rows) || !fits(self.cols, other.cols) {
+            return Err(MatrixError::ShapeMismatch {
+                op,
+                left: self.shape(),
+                right: other.shape(),
+            });
+        }
+
+        let cols = self.cols;
+        for (r, row) in self.data.chunks_mut(cols.max(1)).enumerate() {
+            let theirs = other.row(if other.rows == 1 { 0 } else { r });
+            if other.cols == 1 {
+                for a in row {
+                    *a = f(*a, theirs[0]);
+                }
+            } else {
+                for (a, &b) in row.iter_mut().zip(theirs) {
+                    *a = f(*a, b);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// `add` with `other` broadcast as in `try_broadcast_with`, e.g. adding
+    /// a row of biases to every sample in a batch.
+    pub fn broadcast_add(&self, other: &Matrix) -> Matrix {
+        self.try_broadcast_add(other)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_broadcast_add(&self, other: &Matrix) -> Result<Matrix, MatrixError> {
+        self.try_broadcast_with(other, "broadcast_add", |a, b| a + b)
+    }
+
+    pub fn broadcast_add_assign(&mut self, other: &Matrix) {
+        self.try_broadcast_assign_with(other, "broadcast_add", |a, b| a + b)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn broadcast_sub(&self, other: &Matrix) -> Matrix {
+        self.try_broadcast_sub(other)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
 
-        Matrix::from_vec(data)
+    pub fn try_broadcast_sub(&self, other: &Matrix) -> Result<Matrix, MatrixError> {
+        self.try_broadcast_with(other, "broadcast_sub", |a, b| a - b)
     }
 
+    pub fn broadcast_mul(&self, other: &Matrix) -> Matrix {
+        self.try_broadcast_mul(other)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_broadcast_mul(&self, other: &Matrix) -> Result<Matrix, MatrixError> {
+        self.try_broadcast_with(other, "broadcast_mul", |a, b| a * b)
+    }
+
+    pub fn broadcast_div(&self, other: &Matrix) -> Matrix {
+        self.try_broadcast_div(other)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_broadcast_div(&self, other: &Matrix) -> Result<Matrix, MatrixError> {
+        self.try_broadcast_with(other, "broadcast_div", |a, b| a / b)
+    }
+
+    /// Matrix product. The loops run over `BLOCK`-sized tiles, and the
+    /// innermost one adds a scaled row of `other` to a row of the result.
+    /// Both rows are contiguous, so the compiler can vectorize that loop.
+    /// Each output element still sums its products in order of `k`.
     pub fn dot(&self, other: &Matrix) -> Matrix {
-        assert_eq!(self.cols, other.rows, "Dimension mismatch for dot product");
-
-        let data = (0..self.rows)
-            .map(|i| {
-                (0..other.cols)
-                    .map(|j| {
-                        (0..self.cols)
-                            .map(|k| self.get(i, k) * other.get(k, j))
-                            .sum()
-                    })
-                    .collect::<Vec<f32>>()
-            })
-            .collect::<Vec<Vec<f32>>>();
+        self.try_dot(other).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_dot(&self, other: &Matrix) -> Result<Matrix, MatrixError> {
+        if self.cols != other.rows {
+            return Err(MatrixError::ShapeMismatch {
+                op: "dot",
+                left: self.shape(),
+                right: other.shape(),
+            });
+        }
 
-        Matrix::from_vec(data)
+        let (n, m, p) = (self.rows, self.cols, other.cols);
+        let mut result = Matrix::new(n, p);
+        for k0 in (0..m).step_by(BLOCK) {
+            let k1 = (k0 + BLOCK).min(m);
+            for j0 in (0..p).step_by(BLOCK) {
+                let j1 = (j0 + BLOCK).min(p);
+                for i in 0..n {
+                    let a_row = &self.data[i * m + k0..i * m + k1];
+                    let out_row = &mut result.data[i * p + j0..i * p + j1];
+                    for (k, &a) in (k0..k1).zip(a_row) {
+                        let b_row = &other.data[k * p + j0..k * p + j1];
+                        for (out, &b) in out_row.iter_mut().zip(b_row) {
+                            *out += a * b;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(result)
     }
 
     pub fn map<F>(&self, f: F) -> Matrix
     where
         F: Fn(f32) -> f32,
     {
-        let data = self
-            .data
-            .iter()
-            .map(|row| row.iter().map(|&val| f(val)).collect())
-            .collect();
+        let mut result = self.clone();
+        result.map_inplace(f);
+        result
+    }
 
-        Matrix::from_vec(data)
+    /// Applies `f` to every element without allocating.
+    pub fn map_inplace<F>(&mut self, f: F)
+    where
+        F: Fn(f32) -> f32,
+    {
+        for x in &mut self.data {
+            *x = f(*x);
+        }
     }
 
     pub fn map_rows<F>(&self, f: F) -> Vec<Vec<f32>>
     where
         F: Fn(&[f32]) -> Vec<f32>,
     {
-        self.data.iter().map(|row| f(row)).collect()
+        self.data.chunks(self.cols).map(f).collect()
     }
 
     pub fn print(&self, label: &str) {
         println!("{} ({}x{}):", label, self.rows, self.cols);
-        for (i, row) in self.data.iter().enumerate() {
-            let formatted: Vec<String> = row.iter().map(|v| format!("{:>7.4}", v)).collect();
+        for i in 0..self.rows {
+            let formatted: Vec<String> =
+                self.row(i).iter().map(|v| format!("{:>7.4}", v)).collect();
             println!("Row {:>3}: {}", i, formatted.join(" "));
         }
     }
 
     pub fn transpose(&self) -> Matrix {
-        let data = (0..self.cols)
-            .map(|j| (0..self.rows).map(|i| self.get(i, j)).collect())
-            .collect();
-        Matrix::from_vec(data)
+        let mut result = Matrix::new(self.cols, self.rows);
+        for i in 0..self.rows {
+            for (j, &value) in self.row(i).iter().enumerate() {
+                result.data[j * self.rows + i] = value;
+            }
+        }
+        result
     }
 
     pub fn sum_rows(&self) -> Matrix {
-        let sums = (0..self.cols)
-            .map(|j| (0..self.rows).map(|i| self.get(i, j)).sum())
-            .collect();
-        Matrix::from_vec(vec![sums])
+        self.sum_axis(Axis::Rows)
     }
 
-    pub fn data(&self) -> &Vec<Vec<f32>> {
-        &self.data
+    /// Sum of all elements.
+    pub fn sum(&self) -> f32 {
+        self.data.iter().sum()
+    }
+
+    /// Mean of all elements.
+    pub fn mean(&self) -> f32 {
+        self.sum() / self.data.len() as f32
+    }
+
+    pub fn sum_axis(&self, axis: Axis) -> Matrix {
+        self.fold_axis(axis, 0.0, |acc, x| acc + x)
+    }
+
+    pub fn mean_axis(&self, axis: Axis) -> Matrix {
+        let count = match axis {
+            Axis::Rows => self.rows,
+            Axis::Cols => self.cols,
+        };
+        let mut sums = self.sum_axis(axis);
+        sums.scale_assign(1.0 / count as f32);
+        sums
+    }
+
+    pub fn max_axis(&self, axis: Axis) -> Matrix {
+        self.fold_axis(axis, f32::NEG_INFINITY, f32::max)
+    }
+
+    /// Index of the largest element of each column (`Axis::Rows`) or each
+    /// row (`Axis::Cols`). Ties go to the first index.
+    pub fn argmax_axis(&self, axis: Axis) -> Vec<usize> {
+        let mut best = match axis {
+            Axis::Rows => vec![(0, f32::NEG_INFINITY); self.cols],
+            Axis::Cols => vec![(0, f32::NEG_INFINITY); self.rows],
+        };
+        for r in 0..self.rows {
+            for (c, &value) in self.row(r).iter().enumerate() {
+                let (slot, index) = match axis {
+                    Axis::Rows => (&mut best[c], r),
+                    Axis::Cols => (&mut best[r], c),
+                };
+                if value > slot.1 {
+                    *slot = (index, value);
+                }
+            }
+        }
+        best.into_iter().map(|(index, _)| index).collect()
+    }
+
+    /// Folds every column (`Axis::Rows`) or row (`Axis::Cols`) into one value.
+    fn fold_axis<F>(&self, axis: Axis, init: f32, f: F) -> Matrix
+    where
+        F: Fn(f32, f32) -> f32,
+    {
+        match axis {
+            Axis::Rows => {
+                let mut result = Matrix::filled(1, self.cols, init);
+                for r in 0..self.rows {
+                    for (acc, &value) in result.data.iter_mut().zip(self.row(r)) {
+                        *acc = f(*acc, value);
+                    }
+                }
+                result
+            }
+            Axis::Cols => Matrix::from_fn(self.rows, 1, |r, _| {
+                self.row(r).iter().fold(init, |acc, &value| f(acc, value))
+            }),
+        }
+    }
+
+    /// Copy of the rows in `range`.
+    pub fn slice_rows(&self, range: Range<usize>) -> Matrix {
+        self.try_slice_rows(range)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_slice_rows(&self, range: Range<usize>) -> Result<Matrix, MatrixError> {
+        if range.start > range.end || range.end > self.rows {
+            return Err(MatrixError::RowRange {
+                start: range.start,
+                end: range.end,
+                rows: self.rows,
+            });
+        }
+        Ok(Matrix {
+            rows: range.len(),
+            cols: self.cols,
+            data: self.data[range.start * self.cols..range.end * self.cols].to_vec(),
+        })
+    }
+
+    /// Stacks matrices with equal column counts on top of each other.
+    pub fn vstack(parts: &[Matrix]) -> Matrix {
+        Self::try_vstack(parts).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_vstack(parts: &[Matrix]) -> Result<Matrix, MatrixError> {
+        let first = parts.first().ok_or(MatrixError::Empty)?;
+        let mut data = Vec::new();
+        for part in parts {
+            if part.cols != first.cols {
+                return Err(MatrixError::ShapeMismatch {
+                    op: "vstack",
+                    left: first.shape(),
+                    right: part.shape(),
+                });
+            }
+            data.extend_from_slice(&part.data);
+        }
+        Ok(Matrix {
+            rows: parts.iter().map(|p| p.rows).sum(),
+            cols: first.cols,
+            data,
+        })
+    }
+
+    /// Places matrices with equal row counts side by side.
+    pub fn hstack(parts: &[Matrix]) -> Matrix {
+        Self::try_hstack(parts).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_hstack(parts: &[Matrix]) -> Result<Matrix, MatrixError> {
+        let first = parts.first().ok_or(MatrixError::Empty)?;
+        if let Some(part) = parts.iter().find(|p| p.rows != first.rows) {
+            return Err(MatrixError::ShapeMismatch {
+                op: "hstack",
+                left: first.shape(),
+                right: part.shape(),
+            });
+        }
+        let cols = parts.iter().map(|p| p.cols).sum();
+        let mut data = Vec::with_capacity(first.rows * cols);
+        for r in 0..first.rows {
+            for part in parts {
+                data.extend_from_slice(part.row(r));
+            }
+        }
+        Ok(Matrix {
+            rows: first.rows,
+            cols,
+            data,
+        })
+    }
+}
+
+// Operators panic on mismatched shapes like the methods they call. `*`
+// between two matrices is the element-wise product; `dot` is the matrix
+// product.
+
+impl ops::Add<&Matrix> for &Matrix {
+    type Output = Matrix;
+
+    fn add(self, rhs: &Matrix) -> Matrix {
+        Matrix::add(self, rhs)
+    }
+}
+
+impl ops::Add<&Matrix> for Matrix {
+    type Output = Matrix;
+
+    fn add(mut self, rhs: &Matrix) -> Matrix {
+        Matrix::add_assign(&mut self, rhs);
+        self
+    }
+}
+
+impl ops::AddAssign<&Matrix> for Matrix {
+    fn add_assign(&mut self, rhs: &Matrix) {
+        Matrix::add_assign(self, rhs);
+    }
+}
+
+impl ops::Sub<&Matrix> for &Matrix {
+    type Output = Matrix;
+
+    fn sub(self, rhs: &Matrix) -> Matrix {
+        Matrix::sub(self, rhs)
+    }
+}
+
+impl ops::Sub<&Matrix> for Matrix {
+    type Output = Matrix;
+
+    fn sub(mut self, rhs: &Matrix) -> Matrix {
+        Matrix::sub_assign(&mut self, rhs);
+        self
+    }
+}
+
+impl ops::SubAssign<&Matrix> for Matrix {
+    fn sub_assign(&mut self, rhs: &Matrix) {
+        Matrix::sub_assign(self, rhs);
+    }
+}
+
+impl ops::Mul<&Matrix> for &Matrix {
+    type Output = Matrix;
+
+    fn mul(self, rhs: &Matrix) -> Matrix {
+        self.hadamard(rhs)
+    }
+}
+
+impl ops::Mul<f32> for &Matrix {
+    type Output = Matrix;
+
+    fn mul(self, rhs: f32) -> Matrix {
+        self.scale(rhs)
+    }
+}
+
+impl ops::Mul<f32> for Matrix {
+    type Output = Matrix;
+
+    fn mul(mut self, rhs: f32) -> Matrix {
+        self.scale_assign(rhs);
+        self
+    }
+}
+
+impl ops::MulAssign<f32> for Matrix {
+    fn mul_assign(&mut self, rhs: f32) {
+        self.scale_assign(rhs);
+    }
+}
+
+impl ops::Div<&Matrix> for &Matrix {
+    type Output = Matrix;
+
+    fn div(self, rhs: &Matrix) -> Matrix {
+        Matrix::div(self, rhs)
+    }
+}
+
+impl ops::Neg for &Matrix {
+    type Output = Matrix;
+
+    fn neg(self) -> Matrix {
+        self.map(|x| -x)
+    }
+}
+
+impl ops::Neg for Matrix {
+    type Output = Matrix;
+
+    fn neg(mut self) -> Matrix {
+        self.map_inplace(|x| -x);
+        self
     }
 }
 
@@ -179,6 +834,199 @@ mod tests {
         assert_eq!(result.get(1, 1), 50.0);
     }
 
+    #[test]
+    fn test_blocked_dot_matches_naive_product() {
+        // Sizes straddle the block edge so partial tiles are covered.
+        let (n, m, p) = (3, BLOCK + 5, BLOCK + 2);
+        let a = Matrix::from_vec(
+            (0..n)
+                .map(|i| {
+                    (0..m)
+                        .map(|k| ((i * 7 + k * 3) % 11) as f32 - 5.0)
+                        .collect()
+                })
+                .collect(),
+        );
+        let b = Matrix::from_vec(
+            (0..m)
+                .map(|k| (0..p).map(|j| ((k * 5 + j) % 13) as f32 * 0.5).collect())
+                .collect(),
+        );
+        let result = a.dot(&b);
+        assert_eq!((result.rows(), result.cols()), (n, p));
+        for i in 0..n {
+            for j in 0..p {
+                let expected: f32 = (0..m).map(|k| a.get(i, k) * b.get(k, j)).sum();
+                assert_eq!(result.get(i, j), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_in_place_ops_and_layout() {
+        let mut m = Matrix::from_vec(vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]]);
+        assert_eq!(m.as_slice(), &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        assert_eq!(m.transpose().as_slice(), &[1.0, 4.0, 2.0, 5.0, 3.0, 6.0]);
+        assert_eq!(m.sum_rows().as_slice(), &[5.0, 7.0, 9.0]);
+
+        m.add_assign(&Matrix::from_vec(vec![vec![1.0; 3]; 2]));
+        m.scale_assign(2.0);
+        m.map_inplace(|x| x - 1.0);
+        m.row_mut(1)[0] = 0.0;
+        assert_eq!(m.row(0), &[3.0, 5.0, 7.0]);
+        assert_eq!(m.row(1), &[0.0, 11.0, 13.0]);
+    }
+
+    #[test]
+    fn test_shape_errors() {
+        let a = Matrix::new(2, 3);
+        let b = Matrix::new(3, 2);
+        assert_eq!(
+            a.try_add(&b).err(),
+            Some(MatrixError::ShapeMismatch {
+                op: "add",
+                left: (2, 3),
+                right: (3, 2),
+            })
+        );
+        assert!(a.try_dot(&a).is_err());
+        assert_eq!(a.try_dot(&b).unwrap().shape(), (2, 2));
+
+        assert_eq!(Matrix::try_from_vec(vec![]).err(), Some(MatrixError::Empty));
+        let ragged = Matrix::try_from_vec(vec![vec![1.0, 2.0], vec![3.0]]);
+        assert_eq!(
+            ragged.err(),
+            Some(MatrixError::RaggedRow {
+                row: 1,
+                expected: 2,
+                found: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn test_checked_get_and_set() {
+        let mut m = Matrix::new(2, 2);
+        assert_eq!(m.try_set(1, 1, 4.0), Ok(()));
+        assert_eq!(m.checked_get(1, 1), Some(4.0));
+        assert_eq!(m.checked_get(2, 0), None);
+        assert_eq!(m.checked_get(0, 2), None);
+        let err = m.try_set(0, 5, 1.0).unwrap_err();
+        assert_eq!(err.to_string(), "index (0, 5) is outside a 2x2 matrix");
+    }
+
+    #[test]
+    fn test_constructors() {
+        assert_eq!(Matrix::zeros(2, 2).as_slice(), &[0.0; 4]);
+        assert_eq!(Matrix::ones(1, 3).as_slice(), &[1.0; 3]);
+        assert_eq!(
+            Matrix::identity(3).as_slice(),
+            &[1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0]
+        );
+        let m = Matrix::from_fn(2, 3, |r, c| (r * 10 + c) as f32);
+        assert_eq!(m.row(1), &[10.0, 11.0, 12.0]);
+        let a = Matrix::from_vec(vec![vec![1.0, 2.0], vec![3.0, 4.0]]);
+        assert_eq!(a.dot(&Matrix::identity(2)).as_slice(), a.as_slice());
+    }
+
+    #[test]
+    fn test_elementwise_and_scalar_ops() {
+        let a = Matrix::from_vec(vec![vec![1.0, 2.0], vec![3.0, 4.0]]);
+        let b = Matrix::from_vec(vec![vec![2.0, 4.0], vec![6.0, 8.0]]);
+        assert_eq!(b.sub(&a).as_slice(), &[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(a.hadamard(&b).as_slice(), &[2.0, 8.0, 18.0, 32.0]);
+        assert_eq!(b.div(&a).as_slice(), &[2.0; 4]);
+        assert_eq!(a.scale(3.0).as_slice(), &[3.0, 6.0, 9.0, 12.0]);
+        assert_eq!(a.add_scalar(-1.0).as_slice(), &[0.0, 1.0, 2.0, 3.0]);
+        assert!(a.try_sub(&Matrix::new(1, 2)).is_err());
+        assert!(a.try_hadamard(&Matrix::new(2, 1)).is_err());
+
+        assert_eq!((&b - &a).as_slice(), b.sub(&a).as_slice());
+        assert_eq!((&a + &b).as_slice(), a.add(&b).as_slice());
+        assert_eq!((&a * &b).as_slice(), a.hadamard(&b).as_slice());
+        assert_eq!((&b / &a).as_slice(), b.div(&a).as_slice());
+        assert_eq!((-&a).as_slice(), &[-1.0, -2.0, -3.0, -4.0]);
+        assert_eq!((a.clone() * 2.0).as_slice(), b.as_slice());
+        let mut c = b.clone() - &a;
+        c += &a;
+        c -= &b;
+        c *= 5.0;
+        assert_eq!(c.as_slice(), &[0.0; 4]);
+    }
+
+    #[test]
+    fn test_broadcasting() {
+        let m = Matrix::from_vec(vec![vec![1.0, 2.0, 3.0], vec![4.0, 5.0, 6.0]]);
+        let row = Matrix::from_vec(vec![vec![10.0, 20.0, 30.0]]);
+        let col = Matrix::from_vec(vec![vec![1.0], vec![2.0]]);
+        assert_eq!(
+            m.broadcast_add(&row).as_slice(),
+            &[11.0, 22.0, 33.0, 14.0, 25.0, 36.0]
+        );
+        assert_eq!(
+            m.broadcast_mul(&col).as_slice(),
+            &[1.0, 2.0, 3.0, 8.0, 10.0, 12.0]
+        );
+        assert_eq!(
+            m.broadcast_sub(&Matrix::ones(1, 1)).as_slice(),
+            &[0.0, 1.0, 2.0, 3.0, 4.0, 5.0]
+        );
+        assert_eq!(m.broadcast_div(&m).as_slice(), &[1.0; 6]);
+        assert_eq!(
+            m.try_broadcast_add(&Matrix::new(1, 2)).err(),
+            Some(MatrixError::ShapeMismatch {
+                op: "broadcast_add",
+                left: (2, 3),
+                right: (1, 2),
+            })
+        );
+        assert!(m.try_broadcast_add(&Matrix::new(3, 1)).is_err());
+    }
+
+    #[test]
+    fn test_reductions() {
+        let m = Matrix::from_vec(vec![vec![1.0, 5.0, 3.0], vec![4.0, 2.0, 6.0]]);
+        assert_eq!(m.sum(), 21.0);
+        assert_eq!(m.mean(), 3.5);
+        assert_eq!(m.sum_axis(Axis::Rows).as_slice(), &[5.0, 7.0, 9.0]);
+        assert_eq!(m.sum_axis(Axis::Cols).as_slice(), &[9.0, 12.0]);
+        assert_eq!(m.sum_axis(Axis::Cols).shape(), (2, 1));
+        assert_eq!(m.mean_axis(Axis::Rows).as_slice(), &[2.5, 3.5, 4.5]);
+        assert_eq!(m.mean_axis(Axis::Cols).as_slice(), &[3.0, 4.0]);
+        assert_eq!(m.max_axis(Axis::Rows).as_slice(), &[4.0, 5.0, 6.0]);
+        assert_eq!(m.max_axis(Axis::Cols).as_slice(), &[5.0, 6.0]);
+        assert_eq!(m.argmax_axis(Axis::Rows), vec![1, 0, 1]);
+        assert_eq!(m.argmax_axis(Axis::Cols), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_slicing_and_stacking() {
+        let m = Matrix::from_fn(4, 2, |r, c| (r * 2 + c) as f32);
+        let top = m.slice_rows(0..1);
+        let rest = m.slice_rows(1..4);
+        assert_eq!(top.row(0), &[0.0, 1.0]);
+        assert_eq!(rest.shape(), (3, 2));
+        assert_eq!(m.slice_rows(2..2).shape(), (0, 2));
+        assert_eq!(
+            m.try_slice_rows(3..5).err(),
+            Some(MatrixError::RowRange {
+                start: 3,
+                end: 5,
+                rows: 4,
+            })
+        );
+
+        let stacked = Matrix::vstack(&[top, rest]);
+        assert_eq!(stacked.as_slice(), m.as_slice());
+        let wide = Matrix::hstack(&[m.clone(), Matrix::ones(4, 1)]);
+        assert_eq!(wide.shape(), (4, 3));
+        assert_eq!(wide.row(2), &[4.0, 5.0, 1.0]);
+
+        assert_eq!(Matrix::try_vstack(&[]).err(), Some(MatrixError::Empty));
+        assert!(Matrix::try_vstack(&[m.clone(), Matrix::new(1, 3)]).is_err());
+        assert!(Matrix::try_hstack(&[m, Matrix::new(3, 1)]).is_err());
+    }
+
     #[test]
     fn test_matrix_map() {
         let m = Matrix::from_vec(vec![vec![-1.0, 0.0], vec![1.0, 2.0]]);
@@ -1,71 +1,278 @@
+use crate::init::Initializer;
 use crate::layer::LayerEnum;
 use crate::matrix::Matrix;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 
+/// Score at or above which a single-output model predicts the positive class.
+pub const DEFAULT_THRESHOLD: f32 = 0.5;
+
+/// Seed used for weights, shuffling and splits unless another is chosen.
+pub const DEFAULT_SEED: u64 = 42;
+
+#[derive(Clone)]
 pub struct Model {
     layers: Vec<LayerEnum>,
+    threshold: f32,
+    seed: u64,
+    /// Source of all randomness inside the model, seeded from `seed`.
+    rng: StdRng,
+    /// Whether `forward_train` applies dropout. See `train` and `eval`.
+    training: bool,
 }
 
 impl Model {
     pub fn new() -> Self {
-        Model { layers: Vec::new() }
+        Self::with_seed(DEFAULT_SEED)
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        Model {
+            layers: Vec::new(),
+            threshold: DEFAULT_THRESHOLD,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            training: false,
+        }
+    }
+
+    /// Seed the model's RNG was last started from. It is saved with the
+    /// model so a run can be replayed.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Restarts the model's RNG from `seed`.
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    pub fn rng_mut(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+
+    /// Switches to training mode, where dropout layers drop inputs.
+    pub fn train(&mut self) {
+        self.training = true;
+    }
+
+    /// Switches to evaluation mode, where dropout passes everything
+    /// through. New models start in this mode.
+    pub fn eval(&mut self) {
+        self.training = false;
+    }
+
+    pub fn is_training(&self) -> bool {
+        self.training
+    }
+
+    /// `train()` when `training` is true, `eval()` otherwise.
+    pub fn set_training(&mut self, training: bool) {
+        self.training = training;
+    }
+
+    /// Decision threshold used by inference and evaluation.
+    pub fn threshold(&self) -> f32 {
+        self.threshold
+    }
+
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold;
     }
 
     pub fn add_layer(&mut self, layer: LayerEnum) {
         self.layers.push(layer);
     }
 
-    pub fn forward(&mut self, input: &Matrix) -> Matrix {
+    /// Input width of the first dense layer, or 0 without dense layers.
+    pub fn input_size(&self) -> usize {
         self.layers
-            .iter_mut()
-            .fold(input.clone(), |acc, layer| layer.forward(&acc))
+            .iter()
+            .find_map(|layer| match layer {
+                LayerEnum::Dense(d) => Some(d.weights.rows()),
+                _ => None,
+            })
+            .unwrap_or(0)
+    }
+
+    /// Width of the last dense layer, i.e. the number of model outputs.
+    pub fn output_size(&self) -> usize {
+        self.layers
+            .iter()
+            .rev()
+            .find_map(|layer| match layer {
+                LayerEnum::Dense(d) => Some(d.weights.cols()),
+                _ => None,
+            })
+            .unwrap_or(0)
+    }
+
+    /// Forward pass for backpropagation, which lets layers cache what their
+    /// backward pass needs. In training mode dropout masks are drawn from
+    /// the model's RNG. Returns the output of every layer in order; layer i
+    /// reads output i - 1.
+    pub fn forward_train(&mut self, input: &Matrix) -> Vec<Matrix> {
+        let mut outputs: Vec<Matrix> = Vec::with_capacity(self.layers.len());
+        for layer in &mut self.layers {
+            let output = layer.forward(
+                outputs.last().unwrap_or(input),
+                self.training,
+                &mut self.rng,
+            );
+            outputs.push(output);
+        }
+        outputs
     }
 
+    /// Inference-only forward pass, always in evaluation mode. It does not
+    /// touch the training caches, so a trained model can be shared, e.g.
+    /// behind an `Arc`, and queried from several threads at once.
+    pub fn predict(&self, input: &Matrix) -> Matrix {
+        self.layers
+            .iter()
+            .fold(input.clone(), |acc, layer| layer.predict(&acc))
+    }
+
+    /// Prints every layer once, each under its own numbered header.
     pub fn print_weights(&self) {
-        for (i, _layer) in self.layers.iter().enumerate() {
-            println!("🔧 Layer {} Weights:", i);
-            for (i, layer) in self.layers.iter().enumerate() {
-                layer.print_weights(i);
-            }
+        println!("🔧 Model weights ({} layers):", self.layers.len());
+        for (i, layer) in self.layers.iter().enumerate() {
+            layer.print_weights(i);
         }
     }
 
+    /// Whether any layer is a batch norm layer, whose training passes
+    /// depend on the whole batch at once.
+    pub fn has_batch_norm(&self) -> bool {
+        self.layers
+            .iter()
+            .any(|layer| matches!(layer, LayerEnum::BatchNorm(_)))
+    }
+
+    pub fn layers(&self) -> &[LayerEnum] {
+        &self.layers
+    }
+
     pub fn layers_mut(&mut self) -> std::slice::IterMut<'_, LayerEnum> {
         self.layers.iter_mut()
     }
 
-    pub fn set_weight(&mut self, layer_index: usize, row: usize, col: usize, value: f32) {
-        if let Some(LayerEnum::Dense(layer)) = self.layers.get_mut(layer_index) {
-            layer.weights.set(row, col, value);
-        } else {
-            println!("⚠️ Layer {} is not a Dense layer.", layer_index);
+    /// Every trainable parameter in layer order, with its latest gradient.
+    pub fn params_mut(&mut self) -> Vec<(&mut Matrix, Option<&Matrix>)> {
+        self.layers
+            .iter_mut()
+            .flat_map(|layer| layer.params_mut())
+            .collect()
+    }
+
+    /// Every gradient slot, matching `params_mut`.
+    pub fn grads_mut(&mut self) -> Vec<&mut Option<Matrix>> {
+        self.layers
+            .iter_mut()
+            .flat_map(|layer| layer.grads_mut())
+            .collect()
+    }
+
+    /// Sets one weight of a dense layer and returns the value it replaced.
+    pub fn set_weight(
+        &mut self,
+        layer_index: usize,
+        row: usize,
+        col: usize,
+        value: f32,
+    ) -> Result<f32, String> {
+        match self.layers.get_mut(layer_index) {
+            Some(LayerEnum::Dense(layer)) => {
+                let old = layer.weights.checked_get(row, col);
+                layer
+                    .weights
+                    .try_set(row, col, value)
+                    .map_err(|e| format!("layer {}: {}", layer_index, e))?;
+                Ok(old.expect("try_set checked the index"))
+            }
+            Some(_) => Err(format!("layer {} is not a dense layer", layer_index)),
+            None => Err(format!(
+                "there is no layer {}; the model has {}",
+                layer_index,
+                self.layers.len()
+            )),
         }
     }
 
-    pub fn randomize_all_weights(&mut self) {
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
+    /// Redraws every dense layer's weights with `init` from the model's RNG
+    /// and sets all biases to `bias`.
+    pub fn initialize_weights(&mut self, init: Initializer, bias: f32) {
         for layer in &mut self.layers {
             if let LayerEnum::Dense(d) = layer {
-                for r in 0..d.weights.rows() {
-                    for c in 0..d.weights.cols() {
-                        d.weights.set(r, c, rng.gen_range(-1.0..1.0));
-                    }
-                }
+                d.initialize(init, bias, &mut self.rng);
             }
         }
-        println!("🔀 All weights randomized.");
     }
 
     pub fn zero_all_weights(&mut self) {
         for layer in &mut self.layers {
             if let LayerEnum::Dense(d) = layer {
-                for r in 0..d.weights.rows() {
-                    for c in 0..d.weights.cols() {
-                        d.weights.set(r, c, 0.0);
-                    }
-                }
+                d.weights.map_inplace(|_| 0.0);
             }
         }
         println!("🧹 All weights set to zero.");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spec::parse_spec;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_set_weight_reports_errors() {
+        let mut model = parse_spec("3-d2-relu", 1).unwrap();
+        assert_eq!(model.set_weight(0, 2, 1, 0.5).map(|_| ()), Ok(()));
+        assert_eq!(model.set_weight(0, 2, 1, 0.25), Ok(0.5));
+        assert!(model.set_weight(0, 3, 0, 1.0).is_err());
+        assert!(model.set_weight(1, 0, 0, 1.0).is_err());
+        assert!(model.set_weight(5, 0, 0, 1.0).is_err());
+    }
+
+    #[test]
+    fn test_predict_is_shared_across_threads() {
+        fn assert_sync<T: Send + Sync>() {}
+        assert_sync::<Model>();
+
+        let mut model = parse_spec("4-d6-tanh-d3-softmax", 2).unwrap();
+        let input = Matrix::from_fn(5, 4, |r, c| ((r + c) % 3) as f32 - 1.0);
+        let expected = model.forward_train(&input).pop().unwrap();
+        assert_eq!(model.predict(&input).as_slice(), expected.as_slice());
+
+        let model = Arc::new(model);
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let model = Arc::clone(&model);
+                let input = input.clone();
+                thread::spawn(move || model.predict(&input))
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap().as_slice(), expected.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_dropout_only_in_training_mode() {
+        let mut model = parse_spec("4-d32-identity-dropout:0.5", 2).unwrap();
+        let input = Matrix::ones(3, 4);
+        assert!(!model.is_training());
+        let expected = model.predict(&input);
+        let eval = model.forward_train(&input).pop().unwrap();
+        assert_eq!(eval.as_slice(), expected.as_slice());
+
+        model.train();
+        let train = model.forward_train(&input).pop().unwrap();
+        assert!(train.as_slice().contains(&0.0));
+        assert_ne!(train.as_slice(), expected.as_slice());
+        assert_eq!(model.predict(&input).as_slice(), expected.as_slice());
+    }
+}
@@ -0,0 +1,243 @@
+use crate::matrix::Matrix;
+use crate::model::Model;
+
+/// Update rule used to apply gradients to the model's parameters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OptimizerKind {
+    Sgd,
+    Momentum {
+        momentum: f32,
+        nesterov: bool,
+    },
+    RmsProp {
+        decay: f32,
+        epsilon: f32,
+    },
+    Adam {
+        beta1: f32,
+        beta2: f32,
+        epsilon: f32,
+    },
+}
+
+impl OptimizerKind {
+    pub fn momentum() -> Self {
+        OptimizerKind::Momentum {
+            momentum: 0.9,
+            nesterov: false,
+        }
+    }
+
+    pub fn nesterov() -> Self {
+        OptimizerKind::Momentum {
+            momentum: 0.9,
+            nesterov: true,
+        }
+    }
+
+    pub fn rms_prop() -> Self {
+        OptimizerKind::RmsProp {
+            decay: 0.9,
+            epsilon: 1e-8,
+        }
+    }
+
+    pub fn adam() -> Self {
+        OptimizerKind::Adam {
+            beta1: 0.9,
+            beta2: 0.999,
+            epsilon: 1e-8,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OptimizerKind::Sgd => "sgd",
+            OptimizerKind::Momentum {
+                nesterov: false, ..
+            } => "momentum",
+            OptimizerKind::Momentum { nesterov: true, .. } => "nesterov",
+            OptimizerKind::RmsProp { .. } => "rmsprop",
+            OptimizerKind::Adam { .. } => "adam",
+        }
+    }
+
+    /// Parses a `name()` into the kind with its default hyperparameters.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sgd" => Some(OptimizerKind::Sgd),
+            "momentum" => Some(OptimizerKind::momentum()),
+            "nesterov" => Some(OptimizerKind::nesterov()),
+            "rmsprop" => Some(OptimizerKind::rms_prop()),
+            "adam" => Some(OptimizerKind::adam()),
+            _ => None,
+        }
+    }
+
+    /// A learning rate that trains the default network reasonably well.
+    pub fn default_learning_rate(&self) -> f32 {
+        match self {
+            OptimizerKind::Sgd => 0.1,
+            OptimizerKind::Momentum { .. } => 0.05,
+            OptimizerKind::RmsProp { .. } | OptimizerKind::Adam { .. } => 0.01,
+        }
+    }
+
+    fn update(
+        &self,
+        param: &mut Matrix,
+        grad: &Matrix,
+        state: &mut ParamState,
+        learning_rate: f32,
+        step: i32,
+    ) {
+        let values = param
+            .as_mut_slice()
+            .iter_mut()
+            .zip(grad.as_slice())
+            .zip(state.first.as_mut_slice().iter_mut())
+            .zip(state.second.as_mut_slice().iter_mut());
+        for (((p, &g), first), second) in values {
+            let (m, v) = (*first, *second);
+            let (delta, m, v) = match *self {
+                OptimizerKind::Sgd => (learning_rate * g, m, v),
+                OptimizerKind::Momentum { momentum, nesterov } => {
+                    let m = momentum * m + g;
+                    let direction = if nesterov { g + momentum * m } else { m };
+                    (learning_rate * direction, m, v)
+                }
+                OptimizerKind::RmsProp { decay, epsilon } => {
+                    let v = decay * v + (1.0 - decay) * g * g;
+                    (learning_rate * g / (v.sqrt() + epsilon), m, v)
+                }
+                OptimizerKind::Adam {
+                    beta1,
+                    beta2,
+                    epsilon,
+                } => {
+                    let m = beta1 * m + (1.0 - beta1) * g;
+                    let v = beta2 * v + (1.0 - beta2) * g * g;
+                    let m_hat = m / (1.0 - beta1.powi(step));
+                    let v_hat = v / (1.0 - beta2.powi(step));
+                    (learning_rate * m_hat / (v_hat.sqrt() + epsilon), m, v)
+                }
+            };
+
+            *p -= delta;
+            *first = m;
+            *second = v;
+        }
+    }
+}
+
+/// Per-parameter buffers: velocity for momentum, the squared-gradient
+/// average for RMSProp, and both moments for Adam.
+#[derive(Debug, Clone)]
+struct ParamState {
+    first: Matrix,
+    second: Matrix,
+}
+
+/// Applies an `OptimizerKind` to a model, keeping one `ParamState` for every
+/// parameter matrix returned by `Model::params_mut`.
+#[derive(Debug, Clone)]
+pub struct Optimizer {
+    kind: OptimizerKind,
+    state: Vec<ParamState>,
+    steps: i32,
+}
+
+impl Optimizer {
+    pub fn new(kind: OptimizerKind) -> Self {
+        Optimizer {
+            kind,
+            state: Vec::new(),
+            steps: 0,
+        }
+    }
+
+    /// Updates every parameter that has a gradient from the last backward pass.
+    pub fn step(&mut self, model: &mut Model, learning_rate: f32) {
+        self.steps += 1;
+        let params = model.params_mut();
+
+        let shapes_match = self.state.len() == params.len()
+            && self
+                .state
+                .iter()
+                .zip(params.iter())
+                .all(|(s, (p, _))| s.first.rows() == p.rows() && s.first.cols() == p.cols());
+        if !shapes_match {
+            self.state = params
+                .iter()
+                .map(|(p, _)| ParamState {
+                    first: Matrix::new(p.rows(), p.cols()),
+                    second: Matrix::new(p.rows(), p.cols()),
+                })
+                .collect();
+            self.steps = 1;
+        }
+
+        for ((param, grad), state) in params.into_iter().zip(self.state.iter_mut()) {
+            if let Some(grad) = grad {
+                self.kind
+                    .update(param, grad, state, learning_rate, self.steps);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(kind: OptimizerKind, steps: usize, learning_rate: f32) -> f32 {
+        // Minimizes f(p) = (p - 3)^2 with gradient 2 * (p - 3).
+        let mut param = Matrix::from_vec(vec![vec![0.0]]);
+        let mut state = ParamState {
+            first: Matrix::new(1, 1),
+            second: Matrix::new(1, 1),
+        };
+        for step in 1..=steps {
+            let grad = Matrix::from_vec(vec![vec![2.0 * (param.get(0, 0) - 3.0)]]);
+            kind.update(&mut param, &grad, &mut state, learning_rate, step as i32);
+        }
+        param.get(0, 0)
+    }
+
+    #[test]
+    fn test_sgd_step() {
+        assert!((run(OptimizerKind::Sgd, 1, 0.1) - 0.6).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_momentum_accumulates_velocity() {
+        // v1 = -6, p1 = 0.6; v2 = 0.9 * -6 - 4.8 = -10.2, p2 = 1.62
+        assert!((run(OptimizerKind::momentum(), 2, 0.1) - 1.62).abs() < 1e-5);
+        // Nesterov looks ahead: p1 = 0.1 * (6 + 0.9 * 6) = 1.14
+        assert!((run(OptimizerKind::nesterov(), 1, 0.1) - 1.14).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_adaptive_first_step_is_scaled() {
+        // Adam's bias-corrected first step moves by almost exactly lr.
+        assert!((run(OptimizerKind::adam(), 1, 0.1) - 0.1).abs() < 1e-5);
+        // RMSProp's first step is lr / sqrt(1 - decay).
+        let expected = 0.1 / 0.1f32.sqrt();
+        assert!((run(OptimizerKind::rms_prop(), 1, 0.1) - expected).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_all_optimizers_converge() {
+        for kind in [
+            OptimizerKind::Sgd,
+            OptimizerKind::momentum(),
+            OptimizerKind::nesterov(),
+            OptimizerKind::rms_prop(),
+            OptimizerKind::adam(),
+        ] {
+            let p = run(kind, 2000, 0.01);
+            assert!((p - 3.0).abs() < 0.05, "{} ended at {}", kind.name(), p);
+        }
+    }
+}
@@ -0,0 +1,236 @@
+//! Spreading batches over CPU cores with scoped threads. Each worker takes a
+//! contiguous shard of the batch rows.
+
+use crate::loss::Loss;
+use crate::matrix::Matrix;
+use crate::model::Model;
+use crate::train::compute_gradients;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::ops::Range;
+use std::thread;
+
+/// Number of threads the machine can run at once, or 1 if unknown.
+pub fn available_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Splits `rows` into at most `threads` contiguous ranges whose sizes
+/// differ by at most one. Unless there is only one row, no range is a
+/// single row, since a one-row batch has no spread for normalization.
+fn shards(rows: usize, threads: usize) -> Vec<Range<usize>> {
+    if rows == 0 {
+        return Vec::new();
+    }
+    let count = threads.min(rows / 2).max(1);
+    let (size, extra) = (rows / count, rows % count);
+    let mut start = 0;
+    (0..count)
+        .map(|i| {
+            let end = start + size + usize::from(i < extra);
+            let shard = start..end;
+            start = end;
+            shard
+        })
+        .collect()
+}
+
+/// `Model::predict` with the rows of `input` split across `threads` worker
+/// threads. Every row is computed exactly as in a single-threaded pass.
+pub fn predict_parallel(model: &Model, input: &Matrix, threads: usize) -> Matrix {
+    let shards = shards(input.rows(), threads);
+    if shards.len() <= 1 {
+        return model.predict(input);
+    }
+    let outputs: Vec<Matrix> = thread::scope(|scope| {
+        let handles: Vec<_> = shards
+            .into_iter()
+            .map(|rows| scope.spawn(move || model.predict(&input.slice_rows(rows))))
+            .collect();
+        handles
+            .into_iter()
+            .map(|h| h.join().expect("prediction thread panicked"))
+            .collect()
+    });
+    Matrix::vstack(&outputs)
+}
+
+/// `compute_gradients` in training mode with the batch split across
+/// `threads` worker threads.
+/// Each worker backpropagates its shard through its own copy of the model.
+/// The shard gradients are then averaged, weighted by shard size, into the
+/// model's gradient slots, ready for `Optimizer::step`. Returns the batch
+/// mean loss.
+///
+/// Every loss is a mean over the batch rows, so without dropout the result
+/// equals the single-threaded gradient up to floating-point rounding. Each
+/// worker's RNG is reseeded from the model's, so shards draw different
+/// dropout masks. Batch norm needs statistics over the whole batch, so
+/// models containing it run single-threaded.
+pub fn parallel_gradients(
+    model: &mut Model,
+    input: &Matrix,
+    target: &Matrix,
+    loss_fn: Loss,
+    threads: usize,
+) -> f32 {
+    let shards = shards(input.rows(), threads);
+    if shards.len() <= 1 || model.has_batch_norm() {
+        return compute_gradients(model, input, target, loss_fn, true).0;
+    }
+
+    let seeds: Vec<u64> = shards.iter().map(|_| model.rng_mut().gen()).collect();
+    let results: Vec<ShardResult> = thread::scope(|scope| {
+        let replica: &Model = model;
+        let handles: Vec<_> = shards
+            .iter()
+            .zip(seeds)
+            .map(|(rows, seed)| {
+                let rows = rows.clone();
+                scope.spawn(move || {
+                    let mut worker = replica.clone();
+                    *worker.rng_mut() = StdRng::seed_from_u64(seed);
+                    let x = input.slice_rows(rows.clone());
+                    let y = target.slice_rows(rows);
+                    let (loss, _) = compute_gradients(&mut worker, &x, &y, loss_fn, true);
+                    ShardResult {
+                        loss,
+                        grads: worker.grads_mut().into_iter().map(|g| g.take()).collect(),
+                    }
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|h| h.join().expect("training thread panicked"))
+            .collect()
+    });
+
+    let total = input.rows() as f32;
+    let mut loss = 0.0;
+    let mut grad_sums: Vec<Option<Matrix>> = Vec::new();
+    for (rows, shard) in shards.iter().zip(results) {
+        let weight = rows.len() as f32 / total;
+        loss += weight * shard.loss;
+        accumulate(&mut grad_sums, shard.grads, weight);
+    }
+    for (slot, grad) in model.grads_mut().into_iter().zip(grad_sums) {
+        *slot = grad;
+    }
+    loss
+}
+
+/// What one worker of `parallel_gradients` sends back.
+struct ShardResult {
+    loss: f32,
+    grads: Vec<Option<Matrix>>,
+}
+
+/// Adds `weight` times each present matrix to the matching entry of `sums`.
+fn accumulate(sums: &mut Vec<Option<Matrix>>, items: Vec<Option<Matrix>>, weight: f32) {
+    sums.resize(items.len(), None);
+    for (sum, item) in sums.iter_mut().zip(items) {
+        let Some(mut item) = item else { continue };
+        item.scale_assign(weight);
+        match sum {
+            Some(sum) => sum.add_assign(&item),
+            None => *sum = Some(item),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::LayerEnum;
+    use crate::spec::parse_spec;
+
+    fn batch(rows: usize, outputs: usize) -> (Matrix, Matrix) {
+        let input = Matrix::from_fn(rows, 4, |r, c| ((r * 7 + c * 3) % 5) as f32 / 2.0 - 1.0);
+        let target = Matrix::from_fn(rows, outputs, |r, c| (r % outputs == c) as u8 as f32);
+        (input, target)
+    }
+
+    #[test]
+    fn test_shards_cover_every_row_once() {
+        assert_eq!(shards(10, 3), vec![0..4, 4..7, 7..10]);
+        assert_eq!(shards(7, 3), vec![0..3, 3..5, 5..7]);
+        assert_eq!(shards(5, 8), vec![0..3, 3..5]);
+        assert_eq!(shards(5, 1), vec![0..5]);
+        assert_eq!(shards(1, 4), vec![0..1]);
+        assert!(shards(0, 4).is_empty());
+    }
+
+    #[test]
+    fn test_predict_parallel_matches_predict() {
+        let model = parse_spec("4-d16-relu-d3-softmax", 5).unwrap();
+        let (input, _) = batch(37, 3);
+        let expected = model.predict(&input);
+        for threads in [1, 2, 4, 64] {
+            let actual = predict_parallel(&model, &input, threads);
+            assert_eq!(
+                actual.as_slice(),
+                expected.as_slice(),
+                "{} threads",
+                threads
+            );
+        }
+    }
+
+    #[test]
+    fn test_parallel_gradients_match_single_thread() {
+        for (spec, loss) in [
+            ("4-d8-tanh-d3-softmax", Loss::CategoricalCrossEntropy),
+            ("4-d8-sigmoid-d3-sigmoid", Loss::MeanSquaredError),
+            (
+                "4-d8-batchnorm-tanh-d3-softmax",
+                Loss::CategoricalCrossEntropy,
+            ),
+        ] {
+            let (input, target) = batch(21, 3);
+            let mut single = parse_spec(spec, 5).unwrap();
+            let (expected_loss, _) = compute_gradients(&mut single, &input, &target, loss, true);
+
+            let mut parallel = parse_spec(spec, 5).unwrap();
+            let loss = parallel_gradients(&mut parallel, &input, &target, loss, 4);
+            assert!((loss - expected_loss).abs() < 1e-6);
+
+            let grads = |model: &mut Model| -> Vec<Matrix> {
+                model
+                    .params_mut()
+                    .into_iter()
+                    .map(|(_, grad)| grad.unwrap().clone())
+                    .collect()
+            };
+            let expected = grads(&mut single);
+            let actual = grads(&mut parallel);
+            assert_eq!(actual.len(), expected.len());
+            for (a, e) in actual.iter().zip(&expected) {
+                for (x, y) in a.as_slice().iter().zip(e.as_slice()) {
+                    assert!((x - y).abs() < 1e-6, "{}: {} vs {}", spec, x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_parallel_gradients_update_running_statistics() {
+        let (input, target) = batch(24, 3);
+        let loss = Loss::CategoricalCrossEntropy;
+        let mut single = parse_spec("4-d8-batchnorm-tanh-d3-softmax", 5).unwrap();
+        let mut parallel = single.clone();
+        compute_gradients(&mut single, &input, &target, loss, true);
+        parallel_gradients(&mut parallel, &input, &target, loss, 4);
+
+        // Batch norm sees the whole batch, so the statistics match exactly.
+        let stats = |model: &Model| match &model.layers()[1] {
+            LayerEnum::BatchNorm(b) => (b.running_mean.clone(), b.running_var.clone()),
+            _ => unreachable!(),
+        };
+        let (expected_mean, expected_var) = stats(&single);
+        let (actual_mean, actual_var) = stats(&parallel);
+        assert!(expected_mean.as_slice().iter().any(|&m| m.abs() > 1e-3));
+        assert_eq!(actual_mean.as_slice(), expected_mean.as_slice());
+        assert_eq!(actual_var.as_slice(), expected_var.as_slice());
+    }
+}
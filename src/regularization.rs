@@ -0,0 +1,218 @@
+//! Weight penalties and gradient clipping, applied by `train_epochs` between
+//! the backward pass and the optimizer step. Penalties only cover the
+//! weights of dense layers; biases and normalization parameters are left
+//! alone.
+
+use crate::layer::LayerEnum;
+use crate::model::Model;
+
+/// Regularization settings for `train_epochs`. The default turns
+/// everything off.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Regularization {
+    /// L1 strength: adds `l1 * sum(|w|)` to the loss.
+    pub l1: f32,
+    /// Coupled L2 strength: adds `l2 / 2 * sum(w^2)` to the loss, so the
+    /// decay goes through the optimizer like any other gradient.
+    pub l2: f32,
+    /// Decoupled weight decay as in AdamW: every step shrinks each weight
+    /// by `learning_rate * weight_decay * w`, bypassing the optimizer's
+    /// adaptive scaling. Reported as `weight_decay / 2 * sum(w^2)` in the
+    /// loss.
+    pub weight_decay: f32,
+    /// Clamps every gradient entry to `[-v, v]`.
+    pub clip_value: Option<f32>,
+    /// Rescales all gradients together so their global L2 norm is at most
+    /// this.
+    pub clip_norm: Option<f32>,
+}
+
+impl Regularization {
+    /// Penalty term for the model's current weights.
+    pub fn penalty(&self, model: &Model) -> f32 {
+        if self.l1 == 0.0 && self.l2 == 0.0 && self.weight_decay == 0.0 {
+            return 0.0;
+        }
+        let (mut abs_sum, mut square_sum) = (0.0, 0.0);
+        for layer in model.layers() {
+            if let LayerEnum::Dense(d) = layer {
+                for &w in d.weights.as_slice() {
+                    abs_sum += w.abs();
+                    square_sum += w * w;
+                }
+            }
+        }
+        self.l1 * abs_sum + 0.5 * (self.l2 + self.weight_decay) * square_sum
+    }
+
+    /// Prepares the gradients from the last backward pass for the optimizer:
+    /// adds the L1 and coupled L2 gradients, clips, and applies decoupled
+    /// weight decay to the weights. Returns the penalty, measured before the
+    /// decay.
+    pub fn apply(&self, model: &mut Model, learning_rate: f32) -> f32 {
+        let penalty = self.penalty(model);
+        self.add_penalty_gradients(model);
+        self.clip(model);
+        if self.weight_decay != 0.0 {
+            let factor = 1.0 - learning_rate * self.weight_decay;
+            for layer in model.layers_mut() {
+                if let LayerEnum::Dense(d) = layer {
+                    d.weights.scale_assign(factor);
+                }
+            }
+        }
+        penalty
+    }
+
+    fn add_penalty_gradients(&self, model: &mut Model) {
+        if self.l1 == 0.0 && self.l2 == 0.0 {
+            return;
+        }
+        for layer in model.layers_mut() {
+            let LayerEnum::Dense(d) = layer else { continue };
+            let Some(grad) = d.grad_weights.as_mut() else {
+                continue;
+            };
+            for (g, &w) in grad.as_mut_slice().iter_mut().zip(d.weights.as_slice()) {
+                // The L1 subgradient at 0 is taken as 0.
+                let sign = if w == 0.0 { 0.0 } else { w.signum() };
+                *g += self.l1 * sign + self.l2 * w;
+            }
+        }
+    }
+
+    fn clip(&self, model: &mut Model) {
+        let mut grads: Vec<_> = model.grads_mut().into_iter().flatten().collect();
+        if let Some(limit) = self.clip_value {
+            for grad in grads.iter_mut() {
+                grad.map_inplace(|g| g.clamp(-limit, limit));
+            }
+        }
+        if let Some(max_norm) = self.clip_norm {
+            let norm = grads
+                .iter()
+                .flat_map(|grad| grad.as_slice())
+                .map(|g| g * g)
+                .sum::<f32>()
+                .sqrt();
+            if norm > max_norm {
+                for grad in grads.iter_mut() {
+                    grad.scale_assign(max_norm / norm);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::DenseLayer;
+    use crate::matrix::Matrix;
+
+    /// One dense layer with weights `[[1, -2], [0, 3]]`, bias gradient
+    /// `[[4, 0]]` and weight gradients of 1.
+    fn model() -> Model {
+        let mut dense = DenseLayer::from_parts(
+            Matrix::from_vec(vec![vec![1.0, -2.0], vec![0.0, 3.0]]),
+            Matrix::zeros(1, 2),
+        );
+        dense.grad_weights = Some(Matrix::ones(2, 2));
+        dense.grad_biases = Some(Matrix::from_vec(vec![vec![4.0, 0.0]]));
+        let mut model = Model::new();
+        model.add_layer(LayerEnum::Dense(dense));
+        model
+    }
+
+    fn dense(model: &Model) -> &DenseLayer {
+        match &model.layers()[0] {
+            LayerEnum::Dense(d) => d,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_penalties_and_their_gradients() {
+        let mut model = model();
+        let reg = Regularization {
+            l1: 0.1,
+            l2: 0.5,
+            ..Regularization::default()
+        };
+        // 0.1 * 6 + 0.25 * 14
+        assert!((reg.apply(&mut model, 0.1) - 4.1).abs() < 1e-6);
+        let d = dense(&model);
+        let expected = [1.0 + 0.1 + 0.5, 1.0 - 0.1 - 1.0, 1.0, 1.0 + 0.1 + 1.5];
+        for (g, e) in d
+            .grad_weights
+            .as_ref()
+            .unwrap()
+            .as_slice()
+            .iter()
+            .zip(expected)
+        {
+            assert!((g - e).abs() < 1e-6, "{} vs {}", g, e);
+        }
+        // Biases are not penalized and the weights are untouched.
+        assert_eq!(d.grad_biases.as_ref().unwrap().as_slice(), &[4.0, 0.0]);
+        assert_eq!(d.weights.as_slice(), &[1.0, -2.0, 0.0, 3.0]);
+        assert_eq!(Regularization::default().penalty(&model), 0.0);
+    }
+
+    #[test]
+    fn test_decoupled_weight_decay_shrinks_weights() {
+        let mut model = model();
+        let reg = Regularization {
+            weight_decay: 0.5,
+            ..Regularization::default()
+        };
+        assert!((reg.apply(&mut model, 0.1) - 3.5).abs() < 1e-6);
+        let d = dense(&model);
+        assert_eq!(d.weights.as_slice(), &[0.95, -1.9, 0.0, 2.85]);
+        assert_eq!(d.grad_weights.as_ref().unwrap().as_slice(), &[1.0; 4]);
+    }
+
+    #[test]
+    fn test_clipping() {
+        let mut model = model();
+        let by_value = Regularization {
+            clip_value: Some(0.5),
+            ..Regularization::default()
+        };
+        by_value.apply(&mut model, 0.1);
+        let d = dense(&model);
+        assert_eq!(d.grad_weights.as_ref().unwrap().as_slice(), &[0.5; 4]);
+        assert_eq!(d.grad_biases.as_ref().unwrap().as_slice(), &[0.5, 0.0]);
+
+        // The global norm is sqrt(4 * 1 + 16) = sqrt(20).
+        let mut model = self::model();
+        let by_norm = Regularization {
+            clip_norm: Some(1.0),
+            ..Regularization::default()
+        };
+        by_norm.apply(&mut model, 0.1);
+        let norm: f32 = model
+            .grads_mut()
+            .into_iter()
+            .flatten()
+            .flat_map(|g| g.as_slice().to_vec())
+            .map(|g| g * g)
+            .sum::<f32>()
+            .sqrt();
+        assert!((norm - 1.0).abs() < 1e-6);
+        let d = dense(&model);
+        assert!((d.grad_biases.as_ref().unwrap().get(0, 0) - 4.0 / 20f32.sqrt()).abs() < 1e-6);
+
+        // Gradients already within the limit are left alone.
+        let mut model = self::model();
+        let loose = Regularization {
+            clip_norm: Some(10.0),
+            ..Regularization::default()
+        };
+        loose.apply(&mut model, 0.1);
+        assert_eq!(
+            dense(&model).grad_weights.as_ref().unwrap().as_slice(),
+            &[1.0; 4]
+        );
+    }
+}
@@ -0,0 +1,325 @@
+//! Learning-rate schedules. `train_epochs` asks a `Scheduler` for the rate
+//! at the start of every epoch and reports the epoch's loss back to it.
+
+use std::f32::consts::PI;
+use std::fmt;
+
+/// How the learning rate changes from epoch to epoch, relative to the base
+/// rate in `TrainConfig`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LrSchedule {
+    /// The base rate throughout.
+    Constant,
+    /// Multiplies the rate by `gamma` every `step_size` epochs.
+    StepDecay { step_size: usize, gamma: f32 },
+    /// Multiplies the rate by `gamma` every epoch.
+    Exponential { gamma: f32 },
+    /// Cosine annealing from the base rate towards 0 over `period` epochs,
+    /// then restarting at the base rate with the period multiplied by
+    /// `multiplier` (SGDR).
+    CosineWarmRestarts { period: usize, multiplier: usize },
+    /// Ramps linearly from `base / epochs` up to the base rate over the
+    /// first `epochs` epochs, then stays there.
+    LinearWarmup { epochs: usize },
+    /// Multiplies the rate by `factor` once the loss has not improved for
+    /// more than `patience` epochs.
+    ReduceOnPlateau { factor: f32, patience: usize },
+}
+
+impl LrSchedule {
+    pub fn step_decay() -> Self {
+        LrSchedule::StepDecay {
+            step_size: 10,
+            gamma: 0.5,
+        }
+    }
+
+    pub fn exponential() -> Self {
+        LrSchedule::Exponential { gamma: 0.95 }
+    }
+
+    pub fn cosine_warm_restarts() -> Self {
+        LrSchedule::CosineWarmRestarts {
+            period: 10,
+            multiplier: 2,
+        }
+    }
+
+    pub fn linear_warmup() -> Self {
+        LrSchedule::LinearWarmup { epochs: 5 }
+    }
+
+    pub fn reduce_on_plateau() -> Self {
+        LrSchedule::ReduceOnPlateau {
+            factor: 0.5,
+            patience: 3,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LrSchedule::Constant => "constant",
+            LrSchedule::StepDecay { .. } => "step",
+            LrSchedule::Exponential { .. } => "exponential",
+            LrSchedule::CosineWarmRestarts { .. } => "cosine",
+            LrSchedule::LinearWarmup { .. } => "warmup",
+            LrSchedule::ReduceOnPlateau { .. } => "plateau",
+        }
+    }
+
+    /// Parses a name followed by optional `:`-separated parameters, as
+    /// written by `Display`, e.g. `step:10:0.5` or `cosine`. Missing
+    /// parameters take their defaults.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let mut parts = name.split(':');
+        let kind = parts.next()?;
+        let params: Vec<&str> = parts.collect();
+        // Parameter `i`, or `default` if it was left out.
+        fn param<T: std::str::FromStr>(params: &[&str], i: usize, default: T) -> Option<T> {
+            params.get(i).map_or(Some(default), |p| p.parse().ok())
+        }
+
+        let schedule = match LrSchedule::default_for(kind)? {
+            LrSchedule::Constant => LrSchedule::Constant,
+            LrSchedule::StepDecay { step_size, gamma } => LrSchedule::StepDecay {
+                step_size: param(&params, 0, step_size)?,
+                gamma: param(&params, 1, gamma)?,
+            },
+            LrSchedule::Exponential { gamma } => LrSchedule::Exponential {
+                gamma: param(&params, 0, gamma)?,
+            },
+            LrSchedule::CosineWarmRestarts { period, multiplier } => {
+                LrSchedule::CosineWarmRestarts {
+                    period: param(&params, 0, period)?,
+                    multiplier: param(&params, 1, multiplier)?,
+                }
+            }
+            LrSchedule::LinearWarmup { epochs } => LrSchedule::LinearWarmup {
+                epochs: param(&params, 0, epochs)?,
+            },
+            LrSchedule::ReduceOnPlateau { factor, patience } => LrSchedule::ReduceOnPlateau {
+                factor: param(&params, 0, factor)?,
+                patience: param(&params, 1, patience)?,
+            },
+        };
+        if params.len() > schedule.param_count() || !schedule.is_valid() {
+            return None;
+        }
+        Some(schedule)
+    }
+
+    /// The schedule called `kind` with its default parameters.
+    fn default_for(kind: &str) -> Option<Self> {
+        match kind {
+            "constant" => Some(LrSchedule::Constant),
+            "step" => Some(LrSchedule::step_decay()),
+            "exponential" => Some(LrSchedule::exponential()),
+            "cosine" => Some(LrSchedule::cosine_warm_restarts()),
+            "warmup" => Some(LrSchedule::linear_warmup()),
+            "plateau" => Some(LrSchedule::reduce_on_plateau()),
+            _ => None,
+        }
+    }
+
+    fn param_count(&self) -> usize {
+        match self {
+            LrSchedule::Constant => 0,
+            LrSchedule::Exponential { .. } | LrSchedule::LinearWarmup { .. } => 1,
+            _ => 2,
+        }
+    }
+
+    /// Periods must be at least 1 and decay factors in (0, 1].
+    fn is_valid(&self) -> bool {
+        let factor_ok = |f: f32| f > 0.0 && f <= 1.0;
+        match *self {
+            LrSchedule::Constant => true,
+            LrSchedule::StepDecay { step_size, gamma } => step_size > 0 && factor_ok(gamma),
+            LrSchedule::Exponential { gamma } => factor_ok(gamma),
+            LrSchedule::CosineWarmRestarts { period, multiplier } => period > 0 && multiplier > 0,
+            LrSchedule::LinearWarmup { epochs } => epochs > 0,
+            LrSchedule::ReduceOnPlateau { factor, .. } => factor_ok(factor),
+        }
+    }
+}
+
+impl fmt::Display for LrSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LrSchedule::Constant => write!(f, "{}", self.name()),
+            LrSchedule::StepDecay { step_size, gamma } => {
+                write!(f, "{}:{}:{}", self.name(), step_size, gamma)
+            }
+            LrSchedule::Exponential { gamma } => write!(f, "{}:{}", self.name(), gamma),
+            LrSchedule::CosineWarmRestarts { period, multiplier } => {
+                write!(f, "{}:{}:{}", self.name(), period, multiplier)
+            }
+            LrSchedule::LinearWarmup { epochs } => write!(f, "{}:{}", self.name(), epochs),
+            LrSchedule::ReduceOnPlateau { factor, patience } => {
+                write!(f, "{}:{}:{}", self.name(), factor, patience)
+            }
+        }
+    }
+}
+
+/// Applies an `LrSchedule` to a base rate, keeping the loss history that
+/// reduce-on-plateau needs.
+#[derive(Debug, Clone)]
+pub struct Scheduler {
+    schedule: LrSchedule,
+    base_rate: f32,
+    /// Current rate of reduce-on-plateau.
+    plateau_rate: f32,
+    best_loss: f32,
+    epochs_without_improvement: usize,
+}
+
+impl Scheduler {
+    pub fn new(schedule: LrSchedule, base_rate: f32) -> Self {
+        Scheduler {
+            schedule,
+            base_rate,
+            plateau_rate: base_rate,
+            best_loss: f32::INFINITY,
+            epochs_without_improvement: 0,
+        }
+    }
+
+    /// Learning rate for `epoch`, counting from 0.
+    pub fn rate(&self, epoch: usize) -> f32 {
+        let base = self.base_rate;
+        match self.schedule {
+            LrSchedule::Constant => base,
+            LrSchedule::StepDecay { step_size, gamma } => {
+                base * gamma.powi((epoch / step_size) as i32)
+            }
+            LrSchedule::Exponential { gamma } => base * gamma.powi(epoch as i32),
+            LrSchedule::CosineWarmRestarts { period, multiplier } => {
+                // Walk through the cycles to find the one `epoch` falls in.
+                let (mut start, mut length) = (0, period);
+                while epoch >= start + length {
+                    start += length;
+                    length *= multiplier;
+                }
+                let progress = (epoch - start) as f32 / length as f32;
+                base * 0.5 * (1.0 + (PI * progress).cos())
+            }
+            LrSchedule::LinearWarmup { epochs } => {
+                base * ((epoch + 1) as f32 / epochs as f32).min(1.0)
+            }
+            LrSchedule::ReduceOnPlateau { .. } => self.plateau_rate,
+        }
+    }
+
+    /// Reports the loss at the end of an epoch. Only reduce-on-plateau
+    /// uses it.
+    pub fn observe(&mut self, loss: f32) {
+        let LrSchedule::ReduceOnPlateau { factor, patience } = self.schedule else {
+            return;
+        };
+        if loss < self.best_loss {
+            self.best_loss = loss;
+            self.epochs_without_improvement = 0;
+        } else {
+            self.epochs_without_improvement += 1;
+            if self.epochs_without_improvement > patience {
+                self.plateau_rate *= factor;
+                self.epochs_without_improvement = 0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rates(schedule: LrSchedule, epochs: usize) -> Vec<f32> {
+        let scheduler = Scheduler::new(schedule, 1.0);
+        (0..epochs).map(|e| scheduler.rate(e)).collect()
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-6, "{:?} vs {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_fixed_schedules() {
+        assert_close(&rates(LrSchedule::Constant, 3), &[1.0, 1.0, 1.0]);
+        let step = LrSchedule::StepDecay {
+            step_size: 2,
+            gamma: 0.5,
+        };
+        assert_close(&rates(step, 5), &[1.0, 1.0, 0.5, 0.5, 0.25]);
+        let exponential = LrSchedule::Exponential { gamma: 0.9 };
+        assert_close(&rates(exponential, 3), &[1.0, 0.9, 0.81]);
+        let warmup = LrSchedule::LinearWarmup { epochs: 4 };
+        assert_close(&rates(warmup, 6), &[0.25, 0.5, 0.75, 1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_cosine_restarts_with_growing_periods() {
+        let cosine = LrSchedule::CosineWarmRestarts {
+            period: 2,
+            multiplier: 2,
+        };
+        // Cycles cover epochs 0-1, 2-5 and 6-13.
+        let half = 0.5 * (1.0 + (PI / 4.0).cos());
+        let expected = [1.0, 0.5, 1.0, half, 0.5, 1.0 - half, 1.0];
+        assert_close(&rates(cosine, 7), &expected);
+    }
+
+    #[test]
+    fn test_reduce_on_plateau() {
+        let mut scheduler = Scheduler::new(
+            LrSchedule::ReduceOnPlateau {
+                factor: 0.1,
+                patience: 1,
+            },
+            1.0,
+        );
+        let mut seen = Vec::new();
+        for (epoch, loss) in [5.0, 4.0, 4.5, 4.0, 3.0, 3.5, 3.5].into_iter().enumerate() {
+            seen.push(scheduler.rate(epoch));
+            scheduler.observe(loss);
+        }
+        // Two epochs without beating 4.0, then two without beating 3.0.
+        assert_close(&seen, &[1.0, 1.0, 1.0, 1.0, 0.1, 0.1, 0.1]);
+        assert!((scheduler.rate(7) - 0.01).abs() < 1e-8);
+    }
+
+    #[test]
+    fn test_names_round_trip() {
+        for schedule in [
+            LrSchedule::Constant,
+            LrSchedule::step_decay(),
+            LrSchedule::exponential(),
+            LrSchedule::cosine_warm_restarts(),
+            LrSchedule::linear_warmup(),
+            LrSchedule::reduce_on_plateau(),
+        ] {
+            assert_eq!(LrSchedule::from_name(&schedule.to_string()), Some(schedule));
+            assert_eq!(LrSchedule::from_name(schedule.name()), Some(schedule));
+        }
+        assert_eq!(
+            LrSchedule::from_name("step:3"),
+            Some(LrSchedule::StepDecay {
+                step_size: 3,
+                gamma: 0.5
+            })
+        );
+        for bad in [
+            "linear",
+            "step:0",
+            "exponential:1.5",
+            "warmup:x",
+            "constant:1",
+            "plateau:0.5:2:1",
+        ] {
+            assert_eq!(LrSchedule::from_name(bad), None, "{}", bad);
+        }
+    }
+}
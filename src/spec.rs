@@ -0,0 +1,252 @@
+//! Compact architecture specs such as `10-d16-relu-d16-relu-d1-sigmoid`.
+//!
+//! The first token is the input width. After that, tokens separated by `-` add
+//! one layer each:
+//!
+//! * `d16` adds a dense layer with 16 outputs, taking its input width from
+//!   the layer before it.
+//! * `d16x8` adds a dense layer with an explicit input width of 16 and 8
+//!   outputs. The input width must match the previous layer.
+//! * An activation name adds an activation layer, e.g. `relu`, `tanh` or
+//!   `leaky_relu:0.2`.
+//! * `softmax` adds a softmax layer.
+//! * `batchnorm` and `layernorm` add a batch or layer normalization layer
+//!   as wide as the layer before it.
+//! * `dropout:0.2` adds a dropout layer that zeroes 20% of its inputs while
+//!   training.
+//!
+//! A `-` straight after a `:` is a minus sign rather than a separator, so
+//! `leaky_relu:-0.1` is a single token.
+
+use crate::activation::Activation;
+use crate::dataset::Task;
+use crate::layer::{
+    ActivationLayer, BatchNormLayer, DenseLayer, DropoutLayer, LayerEnum, LayerNormLayer,
+    SoftmaxLayer,
+};
+use crate::model::Model;
+
+/// Hidden layers of the network the UI starts with: four sigmoid layers of
+/// 8 neurons. The output head depends on the task.
+pub const DEFAULT_HIDDEN_SPEC: &str = "10-d8-sigmoid-d8-sigmoid-d8-sigmoid-d8-sigmoid";
+
+/// The default network with an output head for `task`: one sigmoid output
+/// for primality, a softmax over the classes otherwise.
+pub fn default_spec(task: Task) -> String {
+    let head = if task.num_classes() == 1 {
+        "sigmoid"
+    } else {
+        "softmax"
+    };
+    format!("{}-d{}-{}", DEFAULT_HIDDEN_SPEC, task.num_classes(), head)
+}
+
+/// Builds a model from a spec string, drawing its weights from an RNG
+/// seeded with `seed`.
+pub fn parse_spec(spec: &str, seed: u64) -> Result<Model, String> {
+    let mut tokens = split_tokens(spec.trim()).into_iter().map(str::trim);
+
+    let input = tokens.next().unwrap_or("");
+    let mut width: usize = match input.parse() {
+        Ok(n) if n > 0 => n,
+        _ => {
+            return Err(format!(
+                "spec must start with the input width, found '{}'",
+                input
+            ))
+        }
+    };
+
+    let mut model = Model::with_seed(seed);
+    for (position, token) in tokens.enumerate() {
+        let position = position + 2;
+        if let Some(sizes) = token.strip_prefix('d').filter(|s| starts_with_digit(s)) {
+            let (declared_input, outputs) = parse_dense(sizes)
+                .ok_or_else(|| format!("token {} '{}': invalid dense layer", position, token))?;
+            if let Some(declared) = declared_input {
+                if declared != width {
+                    return Err(format!(
+                        "token {} '{}': dense layer expects {} inputs but the previous layer produces {}",
+                        position, token, declared, width
+                    ));
+                }
+            }
+            let layer = DenseLayer::new(width, outputs, model.rng_mut());
+            model.add_layer(LayerEnum::Dense(layer));
+            width = outputs;
+        } else if token == "softmax" {
+            model.add_layer(LayerEnum::Softmax(SoftmaxLayer::new()));
+        } else if token == "batchnorm" {
+            model.add_layer(LayerEnum::BatchNorm(BatchNormLayer::new(width)));
+        } else if token == "layernorm" {
+            model.add_layer(LayerEnum::LayerNorm(LayerNormLayer::new(width)));
+        } else if let Some(rate) = token.strip_prefix("dropout:") {
+            let layer = rate
+                .parse::<f32>()
+                .map_err(|e| e.to_string())
+                .and_then(DropoutLayer::new)
+                .map_err(|e| format!("token {} '{}': {}", position, token, e))?;
+            model.add_layer(LayerEnum::Dropout(layer));
+        } else if let Some(activation) = Activation::from_name(token) {
+            model.add_layer(LayerEnum::Activation(ActivationLayer::new(activation)));
+        } else {
+            return Err(format!(
+                "token {} '{}': expected dN, dNxM, softmax, batchnorm, layernorm, dropout:RATE or an activation name",
+                position, token
+            ));
+        }
+    }
+
+    if model.output_size() == 0 {
+        return Err("spec has no dense layer".to_string());
+    }
+    Ok(model)
+}
+
+/// Splits a spec on the `-` separators, keeping a `-` that follows a `:`
+/// as the sign of a parameter.
+fn split_tokens(spec: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    for (i, c) in spec.char_indices() {
+        if c == '-' && !spec[start..i].ends_with(':') {
+            tokens.push(&spec[start..i]);
+            start = i + 1;
+        }
+    }
+    tokens.push(&spec[start..]);
+    tokens
+}
+
+fn starts_with_digit(s: &str) -> bool {
+    s.chars().next().is_some_and(|c| c.is_ascii_digit())
+}
+
+/// Parses `16` or `16x8` into an optional input width and the output width.
+fn parse_dense(sizes: &str) -> Option<(Option<usize>, usize)> {
+    let (input, output) = match sizes.split_once('x') {
+        Some((input, output)) => (Some(input.parse().ok()?), output),
+        None => (None, sizes),
+    };
+    let output: usize = output.parse().ok()?;
+    if output == 0 || input == Some(0) {
+        return None;
+    }
+    Some((input, output))
+}
+
+/// Checks that every dense and normalization layer takes as many inputs as
+/// the dense layer before it produces.
+pub fn check_layer_sizes(model: &Model) -> Result<(), String> {
+    let mut width: Option<usize> = None;
+    for (i, layer) in model.layers().iter().enumerate() {
+        let (inputs, outputs) = match layer {
+            LayerEnum::Dense(d) => (d.weights.rows(), d.weights.cols()),
+            LayerEnum::BatchNorm(b) => (b.size(), b.size()),
+            LayerEnum::LayerNorm(l) => (l.size(), l.size()),
+            _ => continue,
+        };
+        if let Some(w) = width {
+            if inputs != w {
+                return Err(format!(
+                    "layer {} expects {} inputs but the previous layer produces {}",
+                    i, inputs, w
+                ));
+            }
+        }
+        width = Some(outputs);
+    }
+    Ok(())
+}
+
+/// Describes a model in spec form, e.g. for display. Parsing the result
+/// gives the same architecture with new weights.
+pub fn model_spec(model: &Model) -> String {
+    let mut parts = vec![model.input_size().to_string()];
+    for layer in model.layers() {
+        parts.push(match layer {
+            LayerEnum::Dense(d) => format!("d{}", d.weights.cols()),
+            LayerEnum::Activation(a) => a.activation().to_string(),
+            LayerEnum::Softmax(_) => "softmax".to_string(),
+            LayerEnum::Dropout(d) => format!("dropout:{}", d.rate()),
+            LayerEnum::BatchNorm(_) => "batchnorm".to_string(),
+            LayerEnum::LayerNorm(_) => "layernorm".to_string(),
+        });
+    }
+    parts.join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::DEFAULT_SEED;
+
+    #[test]
+    fn test_parse_spec_round_trip() {
+        for spec in [
+            DEFAULT_HIDDEN_SPEC,
+            "10-d16-relu-d16-leaky_relu:0.2-d1-sigmoid",
+            "10-d16-leaky_relu:-0.1-d1-sigmoid",
+            "10-d32-gelu-d13-softmax",
+            "10-d16-relu-dropout:0.25-d1-sigmoid",
+            "10-batchnorm-d16-batchnorm-relu-d8-layernorm-tanh-d3-softmax",
+        ] {
+            let model = parse_spec(spec, DEFAULT_SEED).unwrap();
+            assert_eq!(model_spec(&model), spec);
+        }
+        let model = parse_spec("10-d16-tanh-d3-softmax", DEFAULT_SEED).unwrap();
+        assert_eq!(model.layers().len(), 4);
+        assert_eq!(model.output_size(), 3);
+
+        // Negative parameters survive the trip through model_spec.
+        let mut model = parse_spec("4-d2", DEFAULT_SEED).unwrap();
+        model.add_layer(LayerEnum::Activation(ActivationLayer::new(
+            Activation::LeakyRelu(-0.1),
+        )));
+        let spec = model_spec(&model);
+        assert_eq!(spec, "4-d2-leaky_relu:-0.1");
+        assert_eq!(model_spec(&parse_spec(&spec, DEFAULT_SEED).unwrap()), spec);
+    }
+
+    #[test]
+    fn test_same_seed_gives_same_weights() {
+        let weights = |seed| {
+            let model = parse_spec(DEFAULT_HIDDEN_SPEC, seed).unwrap();
+            crate::storage::model_to_string(&model)
+        };
+        assert_eq!(weights(7), weights(7));
+        assert_ne!(weights(7), weights(8));
+    }
+
+    #[test]
+    fn test_explicit_dense_sizes_are_checked() {
+        assert!(parse_spec("10-d10x16-relu-d16x1", DEFAULT_SEED).is_ok());
+        let err = parse_spec("10-d16-relu-d8x1", DEFAULT_SEED).err().unwrap();
+        assert!(err.contains("expects 8 inputs"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_spec_errors() {
+        assert!(parse_spec("", DEFAULT_SEED).is_err());
+        assert!(parse_spec("d8-relu", DEFAULT_SEED).is_err());
+        assert!(parse_spec("10-relu", DEFAULT_SEED).is_err());
+        assert!(parse_spec("10-d0", DEFAULT_SEED).is_err());
+        assert!(parse_spec("10-dx", DEFAULT_SEED).is_err());
+        let err = parse_spec("10-d8-swish-d1", DEFAULT_SEED).err().unwrap();
+        assert!(err.contains("token 3 'swish'"), "{}", err);
+        let err = parse_spec("10-d8-dropout:1-d1", DEFAULT_SEED)
+            .err()
+            .unwrap();
+        assert!(err.contains("token 3 'dropout:1'"), "{}", err);
+        assert!(parse_spec("10-d8-dropout:x-d1", DEFAULT_SEED).is_err());
+    }
+
+    #[test]
+    fn test_check_layer_sizes() {
+        let mut model = parse_spec("4-d3-relu", DEFAULT_SEED).unwrap();
+        assert!(check_layer_sizes(&model).is_ok());
+        let layer = DenseLayer::new(2, 1, model.rng_mut());
+        model.add_layer(LayerEnum::Dense(layer));
+        assert!(check_layer_sizes(&model).is_err());
+    }
+}
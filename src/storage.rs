@@ -0,0 +1,265 @@
+use crate::activation::Activation;
+use crate::layer::{
+    ActivationLayer, BatchNormLayer, DenseLayer, DropoutLayer, LayerEnum, LayerNormLayer,
+    SoftmaxLayer,
+};
+use crate::matrix::Matrix;
+use crate::model::Model;
+use crate::spec::check_layer_sizes;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// First line of every saved model file.
+const HEADER: &str = "simplest-ai-model 1";
+
+/// Writes the model to `path` in the plain-text model format.
+pub fn save_model(model: &Model, path: &Path) -> io::Result<()> {
+    fs::write(path, model_to_string(model))
+}
+
+/// Reads a model previously written by `save_model`.
+pub fn load_model(path: &Path) -> io::Result<Model> {
+    let text = fs::read_to_string(path)?;
+    parse_model(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Serializes a model. Floats are written with `{}`, which prints the
+/// shortest representation that parses back to the same bits.
+pub fn model_to_string(model: &Model) -> String {
+    let mut out = String::new();
+    out.push_str(HEADER);
+    out.push('\n');
+    out.push_str(&format!("threshold {}\n", model.threshold()));
+    out.push_str(&format!("seed {}\n", model.seed()));
+
+    for layer in model.layers() {
+        match layer {
+            LayerEnum::Dense(d) => {
+                out.push_str(&format!(
+                    "dense {} {}\n",
+                    d.weights.rows(),
+                    d.weights.cols()
+                ));
+                for r in 0..d.weights.rows() {
+                    push_row(&mut out, d.weights.row(r));
+                }
+                push_row(&mut out, d.biases.row(0));
+            }
+            LayerEnum::Activation(a) => {
+                out.push_str(&format!("activation {}\n", a.activation()));
+            }
+            LayerEnum::Softmax(_) => out.push_str("softmax\n"),
+            LayerEnum::Dropout(d) => out.push_str(&format!("dropout {}\n", d.rate())),
+            LayerEnum::BatchNorm(b) => {
+                out.push_str(&format!("batchnorm {}\n", b.size()));
+                push_row(&mut out, b.gamma.row(0));
+                push_row(&mut out, b.beta.row(0));
+                push_row(&mut out, b.running_mean.row(0));
+                push_row(&mut out, b.running_var.row(0));
+            }
+            LayerEnum::LayerNorm(l) => {
+                out.push_str(&format!("layernorm {}\n", l.size()));
+                push_row(&mut out, l.gamma.row(0));
+                push_row(&mut out, l.beta.row(0));
+            }
+        }
+    }
+
+    out
+}
+
+fn push_row(out: &mut String, row: &[f32]) {
+    let values: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+    out.push_str(&values.join(" "));
+    out.push('\n');
+}
+
+/// Parses the text produced by `model_to_string`.
+pub fn parse_model(text: &str) -> Result<Model, String> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, l)| (i + 1, l.trim()))
+        .filter(|(_, l)| !l.is_empty());
+
+    match lines.next() {
+        Some((_, HEADER)) => {}
+        _ => return Err(format!("missing header '{}'", HEADER)),
+    }
+
+    let mut model = Model::new();
+    while let Some((line_no, line)) = lines.next() {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        match parts.as_slice() {
+            ["dense", rows, cols] => {
+                let rows = parse_size(rows, line_no)?;
+                let cols = parse_size(cols, line_no)?;
+                let mut weights = Vec::with_capacity(rows);
+                for _ in 0..rows {
+                    weights.push(read_row(&mut lines, cols)?);
+                }
+                let biases = read_row(&mut lines, cols)?;
+                model.add_layer(LayerEnum::Dense(DenseLayer::from_parts(
+                    Matrix::from_vec(weights),
+                    Matrix::from_vec(vec![biases]),
+                )));
+            }
+            ["activation", name] => {
+                let activation = Activation::from_name(name)
+                    .ok_or_else(|| format!("line {}: unknown activation '{}'", line_no, name))?;
+                model.add_layer(LayerEnum::Activation(ActivationLayer::new(activation)));
+            }
+            ["threshold", value] => {
+                let threshold = value
+                    .parse::<f32>()
+                    .map_err(|e| format!("line {}: {}", line_no, e))?;
+                model.set_threshold(threshold);
+            }
+            ["seed", value] => {
+                let seed = value
+                    .parse::<u64>()
+                    .map_err(|e| format!("line {}: {}", line_no, e))?;
+                model.reseed(seed);
+            }
+            ["softmax"] => model.add_layer(LayerEnum::Softmax(SoftmaxLayer::new())),
+            ["batchnorm", size] => {
+                let size = parse_size(size, line_no)?;
+                let mut row = || read_row(&mut lines, size).map(|r| Matrix::from_vec(vec![r]));
+                let (gamma, beta) = (row()?, row()?);
+                let (running_mean, running_var) = (row()?, row()?);
+                model.add_layer(LayerEnum::BatchNorm(BatchNormLayer::from_parts(
+                    gamma,
+                    beta,
+                    running_mean,
+                    running_var,
+                )));
+            }
+            ["layernorm", size] => {
+                let size = parse_size(size, line_no)?;
+                let gamma = read_row(&mut lines, size)?;
+                let beta = read_row(&mut lines, size)?;
+                model.add_layer(LayerEnum::LayerNorm(LayerNormLayer::from_parts(
+                    Matrix::from_vec(vec![gamma]),
+                    Matrix::from_vec(vec![beta]),
+                )));
+            }
+            ["dropout", rate] => {
+                let layer = rate
+                    .parse::<f32>()
+                    .map_err(|e| e.to_string())
+                    .and_then(DropoutLayer::new)
+                    .map_err(|e| format!("line {}: {}", line_no, e))?;
+                model.add_layer(LayerEnum::Dropout(layer));
+            }
+            _ => return Err(format!("line {}: unexpected '{}'", line_no, line)),
+        }
+    }
+
+    check_layer_sizes(&model)?;
+    Ok(model)
+}
+
+fn parse_size(value: &str, line_no: usize) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("line {}: invalid size '{}'", line_no, value)),
+    }
+}
+
+fn read_row<'a, I>(lines: &mut I, cols: usize) -> Result<Vec<f32>, String>
+where
+    I: Iterator<Item = (usize, &'a str)>,
+{
+    let (line_no, line) = lines
+        .next()
+        .ok_or_else(|| "unexpected end of file".to_string())?;
+    let row = line
+        .split_whitespace()
+        .map(|v| v.parse::<f32>())
+        .collect::<Result<Vec<f32>, _>>()
+        .map_err(|e| format!("line {}: {}", line_no, e))?;
+    if row.len() != cols {
+        return Err(format!(
+            "line {}: expected {} values, found {}",
+            line_no,
+            cols,
+            row.len()
+        ));
+    }
+    Ok(row)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn sample_model() -> Model {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut model = Model::new();
+        model.add_layer(LayerEnum::Dense(DenseLayer::new(10, 4, &mut rng)));
+        model.add_layer(LayerEnum::Activation(ActivationLayer::new(
+            Activation::Sigmoid,
+        )));
+        model.add_layer(LayerEnum::Dense(DenseLayer::new(4, 3, &mut rng)));
+        model.add_layer(LayerEnum::Activation(ActivationLayer::new(
+            Activation::LeakyRelu(0.2),
+        )));
+        model.add_layer(LayerEnum::Dropout(DropoutLayer::new(0.3).unwrap()));
+        model.add_layer(LayerEnum::BatchNorm(BatchNormLayer::from_parts(
+            Matrix::from_vec(vec![vec![1.5, 0.5, 1.0]]),
+            Matrix::from_vec(vec![vec![0.1, -0.2, 0.3]]),
+            Matrix::from_vec(vec![vec![0.25, 0.0, -1.0]]),
+            Matrix::from_vec(vec![vec![2.0, 0.5, 1.0]]),
+        )));
+        model.add_layer(LayerEnum::Dense(DenseLayer::new(3, 2, &mut rng)));
+        model.add_layer(LayerEnum::LayerNorm(LayerNormLayer::from_parts(
+            Matrix::from_vec(vec![vec![0.9, 1.1]]),
+            Matrix::from_vec(vec![vec![0.0, 0.2]]),
+        )));
+        model.add_layer(LayerEnum::Softmax(SoftmaxLayer::new()));
+        model
+    }
+
+    #[test]
+    fn test_round_trip_is_bit_identical() {
+        let mut model = sample_model();
+        if let Some(LayerEnum::Dense(d)) = model.layers_mut().next() {
+            d.biases.set(0, 2, 0.1 + 0.2);
+        }
+        model.set_threshold(0.37);
+        model.reseed(1234);
+        let loaded = parse_model(&model_to_string(&model)).unwrap();
+        assert_eq!(loaded.threshold(), 0.37);
+        assert_eq!(loaded.seed(), 1234);
+
+        let input = Matrix::from_vec(vec![vec![1.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0, 1.0, 1.0]]);
+        let expected = model.predict(&input);
+        let actual = loaded.predict(&input);
+        assert_eq!(expected.get(0, 0).to_bits(), actual.get(0, 0).to_bits());
+        assert_eq!(model_to_string(&model), model_to_string(&loaded));
+    }
+
+    #[test]
+    fn test_threshold_defaults_when_missing() {
+        let model = parse_model("simplest-ai-model 1\nactivation sigmoid\n").unwrap();
+        assert_eq!(model.threshold(), crate::model::DEFAULT_THRESHOLD);
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_input() {
+        assert!(parse_model("not a model").is_err());
+        assert!(parse_model("simplest-ai-model 1\nactivation nope\n").is_err());
+        assert!(parse_model("simplest-ai-model 1\ndropout 1.5\n").is_err());
+        assert!(parse_model("simplest-ai-model 1\nlayernorm 2\n1 1\n").is_err());
+        let mismatched = "simplest-ai-model 1\ndense 1 2\n1 2\n0 0\nlayernorm 3\n1 1 1\n0 0 0\n";
+        assert!(parse_model(mismatched).is_err());
+        assert!(parse_model("simplest-ai-model 1\ndense 2 2\n1 2\n3\n0 0\n").is_err());
+        assert!(parse_model("simplest-ai-model 1\ndense 1 1\n1\n").is_err());
+        assert!(
+            parse_model("simplest-ai-model 1\ndense 1 2\n1 2\n0 0\ndense 1 1\n1\n0\n").is_err()
+        );
+    }
+}
@@ -1,36 +1,292 @@
-use crate::loss::mean_squared_error;
+use crate::activation::Activation;
+use crate::layer::{ActivationLayer, LayerEnum};
+use crate::loss::Loss;
 use crate::matrix::Matrix;
-use crate::model::Model;
+use crate::model::{Model, DEFAULT_SEED};
+use crate::optimizer::{Optimizer, OptimizerKind};
+use crate::parallel::{available_threads, parallel_gradients, predict_parallel};
+use crate::regularization::Regularization;
+use crate::schedule::{LrSchedule, Scheduler};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
 
-pub fn train_step(model: &mut Model, input: &Matrix, target: &Matrix, learning_rate: f32) -> f32 {
-    let output = model.forward(input);
-    let loss = mean_squared_error(&output, target);
+/// Settings for `train_epochs`.
+#[derive(Debug, Clone)]
+pub struct TrainConfig {
+    pub epochs: usize,
+    /// Base learning rate, which `schedule` adjusts every epoch.
+    pub learning_rate: f32,
+    pub schedule: LrSchedule,
+    pub optimizer: OptimizerKind,
+    pub loss: Loss,
+    /// Samples stacked into each `train_step`; 1 gives plain SGD.
+    pub batch_size: usize,
+    /// Seed for the RNG that shuffles the samples each epoch.
+    pub seed: u64,
+    /// Worker threads each batch is split across; 1 trains on the calling
+    /// thread only.
+    pub threads: usize,
+    /// Weight penalties and gradient clipping applied before every
+    /// optimizer step.
+    pub regularization: Regularization,
+    /// Print the loss after every epoch.
+    pub verbose: bool,
+}
 
-    let mut x = input.clone();
-    let mut intermediates = vec![x.clone()];
-    for layer in model.layers_mut() {
-        x = layer.forward(&x);
-        intermediates.push(x.clone());
+impl Default for TrainConfig {
+    fn default() -> Self {
+        TrainConfig {
+            epochs: 10,
+            learning_rate: 0.1,
+            schedule: LrSchedule::Constant,
+            optimizer: OptimizerKind::Sgd,
+            loss: Loss::MeanSquaredError,
+            batch_size: 1,
+            seed: DEFAULT_SEED,
+            threads: 1,
+            regularization: Regularization::default(),
+            verbose: true,
+        }
     }
+}
+
+/// Loss values recorded by `train_epochs`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrainHistory {
+    /// Mean loss of every batch, in the order they ran, including the
+    /// regularization penalty.
+    pub batch_losses: Vec<f32>,
+    /// Sample-weighted mean of the batch losses of every epoch.
+    pub epoch_losses: Vec<f32>,
+    /// Loss on the validation set after every epoch, without the penalty;
+    /// empty when training without one.
+    pub validation_losses: Vec<f32>,
+    /// Learning rate used in every epoch.
+    pub learning_rates: Vec<f32>,
+}
+
+/// Runs one gradient step on a batch stacked as the rows of `input` and
+/// `target`, in training mode whatever mode the model is in. Gradients are
+/// averaged over the batch and regularized before the update. The returned
+/// loss is the batch mean plus the penalty.
+pub fn train_step(
+    model: &mut Model,
+    input: &Matrix,
+    target: &Matrix,
+    loss_fn: Loss,
+    regularization: &Regularization,
+    optimizer: &mut Optimizer,
+    learning_rate: f32,
+) -> f32 {
+    let (loss, _) = compute_gradients(model, input, target, loss_fn, true);
+    let penalty = regularization.apply(model, learning_rate);
+    optimizer.step(model, learning_rate);
+    loss + penalty
+}
+
+/// The forward and backward pass of `train_step` without the update, run
+/// in training mode if `training` is true and in evaluation mode otherwise.
+/// The model's own mode is restored afterwards. Every layer keeps the
+/// gradients of its parameters, and the returned matrices are the gradients
+/// with respect to each layer's input, in layer order.
+pub fn compute_gradients(
+    model: &mut Model,
+    input: &Matrix,
+    target: &Matrix,
+    loss_fn: Loss,
+    training: bool,
+) -> (f32, Vec<Matrix>) {
+    let previous_mode = model.is_training();
+    model.set_training(training);
+    let outputs = model.forward_train(input);
+    let output = outputs.last().unwrap_or(input);
+    let loss = loss_fn.compute(output, target);
 
-    let mut grad = output.add(&target.map(|v| -v)).map(|v| 2.0 * v);
+    // A fused loss gradient already accounts for the last layer.
+    let (grad, skip) = match model.layers().last() {
+        Some(last) => match loss_fn.fused_gradient(last, output, target) {
+            Some(grad) => (grad, 1),
+            None => (loss_fn.gradient(output, target), 0),
+        },
+        None => (loss_fn.gradient(output, target), 0),
+    };
 
-    for (layer, input) in model
-        .layers_mut()
-        .rev()
-        .zip(intermediates.iter().rev().skip(1))
-    {
-        grad = layer.backward(input, &grad);
+    // Each layer's input, from the last layer back to the first.
+    let layer_inputs = outputs.iter().rev().skip(1).chain(std::iter::once(input));
+    let mut grads = vec![grad];
+    for (layer, input) in model.layers_mut().rev().zip(layer_inputs).skip(skip) {
+        let grad = layer.backward(input, grads.last().expect("starts non-empty"));
+        grads.push(grad);
     }
 
-    for layer in model.layers_mut() {
-        layer.update(learning_rate);
+    // Without fusion the first gradient is the loss's, not a layer input's.
+    if skip == 0 {
+        grads.remove(0);
+    }
+    grads.reverse();
+    model.set_training(previous_mode);
+    (loss, grads)
+}
+
+/// Stacks samples into an input matrix with one row per sample and a target
+/// matrix with `outputs` columns. A single output holds the label itself;
+/// wider outputs hold the label one-hot encoded.
+pub fn batch_to_matrices<'a, I>(samples: I, outputs: usize) -> (Matrix, Matrix)
+where
+    I: IntoIterator<Item = &'a (Vec<u8>, u8)>,
+{
+    let (inputs, targets): (Vec<Vec<f32>>, Vec<Vec<f32>>) = samples
+        .into_iter()
+        .map(|(x, y)| {
+            let target = if outputs == 1 {
+                vec![*y as f32]
+            } else {
+                (0..outputs)
+                    .map(|c| if c == *y as usize { 1.0 } else { 0.0 })
+                    .collect()
+            };
+            (x.iter().map(|&b| b as f32).collect(), target)
+        })
+        .unzip();
+    (Matrix::from_vec(inputs), Matrix::from_vec(targets))
+}
+
+/// Mean `loss` of the model over `data`, computed as one batch split across
+/// all cores.
+pub fn evaluate_loss(model: &Model, data: &[(Vec<u8>, u8)], loss: Loss) -> f32 {
+    let (input, target) = batch_to_matrices(data, model.output_size());
+    let output = predict_parallel(model, &input, available_threads());
+    loss.compute(&output, &target)
+}
+
+/// Trains for `config.epochs` passes over `data`, visiting the samples in a
+/// fresh random order each epoch and grouping them into batches of
+/// `config.batch_size`. The loss on `validation` is reported after every
+/// epoch unless it is empty, and drives reduce-on-plateau; without a
+/// validation set the training loss does. The model is left in the mode
+/// it was in.
+pub fn train_epochs(
+    model: &mut Model,
+    data: &[(Vec<u8>, u8)],
+    validation: &[(Vec<u8>, u8)],
+    config: &TrainConfig,
+) -> TrainHistory {
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut order: Vec<usize> = (0..data.len()).collect();
+    let mut history = TrainHistory::default();
+    let batch_size = config.batch_size.max(1);
+    let mut optimizer = Optimizer::new(config.optimizer);
+    let outputs = model.output_size();
+    let mut scheduler = Scheduler::new(config.schedule, config.learning_rate);
+    // Every gradient pass runs in training mode on its own; keep the
+    // caller's mode for everything else.
+    let previous_mode = model.is_training();
+
+    for epoch in 1..=config.epochs {
+        order.shuffle(&mut rng);
+        let learning_rate = scheduler.rate(epoch - 1);
+        history.learning_rates.push(learning_rate);
+
+        let mut total_loss = 0.0;
+        for batch in order.chunks(batch_size) {
+            let (input, target) = batch_to_matrices(batch.iter().map(|&i| &data[i]), outputs);
+            let loss = if config.threads > 1 {
+                let loss = parallel_gradients(model, &input, &target, config.loss, config.threads);
+                let penalty = config.regularization.apply(model, learning_rate);
+                optimizer.step(model, learning_rate);
+                loss + penalty
+            } else {
+                train_step(
+                    model,
+                    &input,
+                    &target,
+                    config.loss,
+                    &config.regularization,
+                    &mut optimizer,
+                    learning_rate,
+                )
+            };
+            history.batch_losses.push(loss);
+            total_loss += loss * batch.len() as f32;
+        }
+
+        let avg_loss = total_loss / data.len().max(1) as f32;
+        history.epoch_losses.push(avg_loss);
+        if validation.is_empty() {
+            if config.verbose {
+                println!(
+                    "📉 Epoch {:>3}/{}: avg loss {:.6}, lr {}",
+                    epoch, config.epochs, avg_loss, learning_rate
+                );
+            }
+            scheduler.observe(avg_loss);
+        } else {
+            // Validation goes through `predict`, which never drops inputs.
+            let validation_loss = evaluate_loss(model, validation, config.loss);
+            history.validation_losses.push(validation_loss);
+            if config.verbose {
+                println!(
+                    "📉 Epoch {:>3}/{}: avg loss {:.6}, validation loss {:.6}, lr {}",
+                    epoch, config.epochs, avg_loss, validation_loss, learning_rate
+                );
+            }
+            scheduler.observe(validation_loss);
+        }
     }
 
-    loss
+    model.set_training(previous_mode);
+    history
+}
+
+/// Batch norm measures the spread of each batch, which a single row does
+/// not have; such batches would quietly use the running statistics instead.
+pub fn check_batch_size(model: &Model, batch_size: usize) -> Result<(), String> {
+    if batch_size < 2 && model.has_batch_norm() {
+        return Err(format!(
+            "batch norm needs a batch size of at least 2, not {}",
+            batch_size
+        ));
+    }
+    Ok(())
 }
 
-pub fn debug_forward_sample(model: &mut Model, input: &[u8], label: u8, number: usize) {
+/// Models trained with `bce_logits` output raw scores. Appends the sigmoid
+/// that turns them into the probabilities prediction and evaluation read,
+/// and returns whether it did.
+pub fn append_logit_sigmoid(model: &mut Model, loss: Loss) -> bool {
+    let from_logits = matches!(
+        loss,
+        Loss::BinaryCrossEntropy {
+            from_logits: true,
+            ..
+        }
+    );
+    if !from_logits || loss.check_output(model.layers().last()).is_err() {
+        return false;
+    }
+    model.add_layer(LayerEnum::Activation(ActivationLayer::new(
+        Activation::Sigmoid,
+    )));
+    true
+}
+
+/// Index of the largest value, used to read a class from softmax output.
+pub fn argmax(values: &[f32]) -> usize {
+    values
+        .iter()
+        .enumerate()
+        .fold((0, f32::NEG_INFINITY), |best, (i, &v)| {
+            if v > best.1 {
+                (i, v)
+            } else {
+                best
+            }
+        })
+        .0
+}
+
+pub fn debug_forward_sample(model: &Model, input: &[u8], label: u8, number: usize) {
     println!("==============================");
     println!("🔢 Number: {}", number);
     println!("📥 Binary Input: {:?}", input);
@@ -38,12 +294,452 @@ pub fn debug_forward_sample(model: &mut Model, input: &[u8], label: u8, number:
     let mut x = Matrix::from_vec(vec![input.iter().map(|&b| b as f32).collect()]);
     x.print("Input");
 
-    for (i, layer) in model.layers_mut().enumerate() {
-        x = layer.forward(&x);
+    for (i, layer) in model.layers().iter().enumerate() {
+        x = layer.predict(&x);
         x.print(&format!("Layer {}", i));
     }
 
-    let prediction = x.get(0, 0);
     println!("🌟 Target: {}", label);
-    println!("🔮 Final Prediction: {:.4}", prediction);
+    if x.cols() == 1 {
+        let score = x.get(0, 0);
+        println!(
+            "🔮 Final Prediction: {:.4} → class {} (threshold {:.2})",
+            score,
+            (score >= model.threshold()) as u8,
+            model.threshold()
+        );
+    } else {
+        let class = argmax(x.row(0));
+        println!(
+            "🔮 Final Prediction: class {} (p = {:.4})",
+            class,
+            x.get(0, class)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::{DenseLayer, SoftmaxLayer};
+    use crate::loss::ClassWeights;
+
+    fn tiny_model() -> Model {
+        let mut model = Model::new();
+        model.add_layer(LayerEnum::Dense(DenseLayer::from_parts(
+            Matrix::from_vec(vec![vec![0.5], vec![-0.5]]),
+            Matrix::new(1, 1),
+        )));
+        model.add_layer(LayerEnum::Activation(ActivationLayer::new(
+            Activation::Sigmoid,
+        )));
+        model
+    }
+
+    #[test]
+    fn test_train_epochs_reduces_loss_and_is_repeatable() {
+        let data = vec![(vec![1, 0], 1), (vec![0, 1], 0), (vec![1, 1], 1)];
+        let config = TrainConfig {
+            epochs: 20,
+            learning_rate: 0.5,
+            seed: 7,
+            ..TrainConfig::default()
+        };
+
+        let first = train_epochs(&mut tiny_model(), &data, &[], &config);
+        let second = train_epochs(&mut tiny_model(), &data, &[], &config);
+
+        let losses = &first.epoch_losses;
+        assert_eq!(losses.len(), 20);
+        assert_eq!(first.batch_losses.len(), 60);
+        assert!(losses.last().unwrap() < losses.first().unwrap());
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_same_seed_replays_a_whole_run() {
+        use crate::dataset::{generate_dataset, select, split_dataset, SplitConfig, Task};
+        use crate::spec::{default_spec, parse_spec};
+        use crate::storage::model_to_string;
+
+        let run = |seed| {
+            let mut model = parse_spec(&default_spec(Task::Primality), seed).unwrap();
+            let data = generate_dataset(Task::Primality);
+            let split = split_dataset(
+                &data,
+                &SplitConfig {
+                    seed,
+                    ..SplitConfig::default()
+                },
+            );
+            let config = TrainConfig {
+                epochs: 2,
+                seed,
+                verbose: false,
+                ..TrainConfig::default()
+            };
+            let history = train_epochs(
+                &mut model,
+                &select(&data, &split.train),
+                &select(&data, &split.validation),
+                &config,
+            );
+            (history, model_to_string(&model))
+        };
+        assert_eq!(run(5), run(5));
+        assert_ne!(run(5).1, run(6).1);
+    }
+
+    #[test]
+    fn test_batch_norm_needs_two_rows_per_batch() {
+        use crate::spec::parse_spec;
+
+        let model = parse_spec("4-d8-batchnorm-relu-d1", 3).unwrap();
+        assert!(check_batch_size(&model, 1).is_err());
+        assert!(check_batch_size(&model, 2).is_ok());
+        assert!(check_batch_size(&tiny_model(), 1).is_ok());
+    }
+
+    #[test]
+    fn test_train_step_runs_in_training_mode() {
+        use crate::spec::parse_spec;
+
+        // A fresh model starts in evaluation mode.
+        let mut model = parse_spec("4-d32-identity-dropout:0.5-d1", 3).unwrap();
+        let input = Matrix::ones(2, 4);
+        let target = Matrix::zeros(2, 1);
+        let mse = Loss::MeanSquaredError;
+        let eval_loss = mse.compute(&model.predict(&input), &target);
+
+        let mut sgd = Optimizer::new(OptimizerKind::Sgd);
+        let none = Regularization::default();
+        let loss = train_step(&mut model, &input, &target, mse, &none, &mut sgd, 0.0);
+        assert_ne!(loss, eval_loss, "dropout was not applied");
+        assert!(!model.is_training());
+
+        let (loss, _) = compute_gradients(&mut model, &input, &target, mse, false);
+        assert_eq!(loss, eval_loss);
+        model.train();
+        compute_gradients(&mut model, &input, &target, mse, false);
+        assert!(model.is_training());
+
+        // train_epochs keeps the caller's mode too.
+        let data = vec![(vec![1, 0, 1, 1], 1), (vec![0, 1, 0, 0], 0)];
+        let config = TrainConfig {
+            epochs: 1,
+            verbose: false,
+            ..TrainConfig::default()
+        };
+        train_epochs(&mut model, &data, &[], &config);
+        assert!(model.is_training());
+        model.eval();
+        train_epochs(&mut model, &data, &[], &config);
+        assert!(!model.is_training());
+    }
+
+    #[test]
+    fn test_batch_gradient_is_mean_of_sample_gradients() {
+        let data = vec![(vec![1, 0], 1), (vec![0, 1], 0), (vec![1, 1], 1)];
+
+        let mut batched = tiny_model();
+        let (input, target) = batch_to_matrices(&data, 1);
+        let mut sgd = Optimizer::new(OptimizerKind::Sgd);
+        let mse = Loss::MeanSquaredError;
+        let none = Regularization::default();
+        let batch_loss = train_step(&mut batched, &input, &target, mse, &none, &mut sgd, 0.3);
+
+        let mut loss_sum = 0.0;
+        let mut grad_sum = Matrix::new(2, 1);
+        for sample in &data {
+            let mut single = tiny_model();
+            let (x, y) = batch_to_matrices(std::iter::once(sample), 1);
+            loss_sum += train_step(&mut single, &x, &y, mse, &none, &mut sgd, 0.3);
+            if let Some(LayerEnum::Dense(d)) = single.layers().first() {
+                grad_sum = grad_sum.add(d.grad_weights.as_ref().unwrap());
+            }
+        }
+
+        assert!((batch_loss - loss_sum / 3.0).abs() < 1e-6);
+        if let Some(LayerEnum::Dense(d)) = batched.layers().first() {
+            let grad = d.grad_weights.as_ref().unwrap();
+            for r in 0..2 {
+                assert!((grad.get(r, 0) - grad_sum.get(r, 0) / 3.0).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn test_single_forward_pass_matches_two_passes() {
+        use crate::spec::parse_spec;
+
+        // The former train_step: a forward pass for the loss, then a second
+        // one to collect each layer's input.
+        fn two_pass_gradients(
+            model: &mut Model,
+            input: &Matrix,
+            target: &Matrix,
+            loss: Loss,
+        ) -> f32 {
+            let output = model.predict(input);
+            let value = loss.compute(&output, target);
+            let mut inputs = vec![input.clone()];
+            let mut rng = StdRng::seed_from_u64(0);
+            for layer in model.layers_mut() {
+                let next = layer.forward(inputs.last().unwrap(), false, &mut rng);
+                inputs.push(next);
+            }
+            let (mut grad, skip) =
+                match loss.fused_gradient(model.layers().last().unwrap(), &output, target) {
+                    Some(grad) => (grad, 1),
+                    None => (loss.gradient(&output, target), 0),
+                };
+            for (layer, x) in model
+                .layers_mut()
+                .rev()
+                .zip(inputs.iter().rev().skip(1))
+                .skip(skip)
+            {
+                grad = layer.backward(x, &grad);
+            }
+            value
+        }
+
+        let bce = Loss::BinaryCrossEntropy {
+            weights: ClassWeights::UNIFORM,
+            from_logits: false,
+        };
+        let data = vec![(vec![1, 0, 1], 1), (vec![0, 1, 1], 0), (vec![1, 1, 0], 2)];
+        for (spec, loss) in [
+            ("3-d4-sigmoid-d1-sigmoid", Loss::MeanSquaredError),
+            ("3-d4-relu-d1-sigmoid", bce),
+            ("3-d4-tanh-d3-softmax", Loss::CategoricalCrossEntropy),
+        ] {
+            let mut single = parse_spec(spec, 9).unwrap();
+            let mut double = parse_spec(spec, 9).unwrap();
+            let (input, target) = batch_to_matrices(&data, single.output_size());
+            let target = target.map(|t| t.min(1.0));
+
+            let (loss_single, _) = compute_gradients(&mut single, &input, &target, loss, true);
+            let loss_double = two_pass_gradients(&mut double, &input, &target, loss);
+            assert_eq!(loss_single.to_bits(), loss_double.to_bits(), "{}", spec);
+            let grads = |model: &mut Model| -> Vec<Vec<f32>> {
+                model
+                    .params_mut()
+                    .into_iter()
+                    .map(|(_, grad)| grad.unwrap().as_slice().to_vec())
+                    .collect()
+            };
+            assert_eq!(grads(&mut single), grads(&mut double), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn test_data_parallel_training_matches_single_thread() {
+        use crate::spec::parse_spec;
+
+        let data: Vec<(Vec<u8>, u8)> = (0..40u16)
+            .map(|n| (crate::dataset::u16_to_bin_vector(n * 25), (n % 3) as u8))
+            .collect();
+        let run = |threads| {
+            let mut model = parse_spec("10-d8-tanh-d3-softmax", 3).unwrap();
+            let config = TrainConfig {
+                epochs: 3,
+                batch_size: 16,
+                loss: Loss::CategoricalCrossEntropy,
+                optimizer: OptimizerKind::adam(),
+                learning_rate: 0.01,
+                threads,
+                verbose: false,
+                ..TrainConfig::default()
+            };
+            let history = train_epochs(&mut model, &data, &data, &config);
+            (history, model)
+        };
+
+        let (single, single_model) = run(1);
+        let (parallel, parallel_model) = run(4);
+        for (a, b) in single.batch_losses.iter().zip(&parallel.batch_losses) {
+            assert!((a - b).abs() < 1e-5, "{} vs {}", a, b);
+        }
+        for (a, b) in single_model.layers().iter().zip(parallel_model.layers()) {
+            if let (LayerEnum::Dense(a), LayerEnum::Dense(b)) = (a, b) {
+                for (x, y) in a.weights.as_slice().iter().zip(b.weights.as_slice()) {
+                    assert!((x - y).abs() < 1e-4, "{} vs {}", x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_every_optimizer_trains_the_model() {
+        let data = vec![(vec![1, 0], 1), (vec![0, 1], 0), (vec![1, 1], 1)];
+        for optimizer in [
+            OptimizerKind::Sgd,
+            OptimizerKind::momentum(),
+            OptimizerKind::nesterov(),
+            OptimizerKind::rms_prop(),
+            OptimizerKind::adam(),
+        ] {
+            let config = TrainConfig {
+                epochs: 30,
+                learning_rate: optimizer.default_learning_rate(),
+                optimizer,
+                ..TrainConfig::default()
+            };
+            let history = train_epochs(&mut tiny_model(), &data, &[], &config);
+            let losses = &history.epoch_losses;
+            assert!(
+                losses.last().unwrap() < losses.first().unwrap(),
+                "{} did not reduce the loss",
+                optimizer.name()
+            );
+        }
+    }
+
+    #[test]
+    fn test_weight_decay_shrinks_weights_and_adds_penalty() {
+        let data = vec![(vec![1, 0], 1), (vec![0, 1], 0), (vec![1, 1], 1)];
+        let squared_weights = |model: &Model| -> f32 {
+            model
+                .layers()
+                .iter()
+                .filter_map(|layer| match layer {
+                    LayerEnum::Dense(d) => Some(d.weights.hadamard(&d.weights).sum()),
+                    _ => None,
+                })
+                .sum()
+        };
+        let run = |regularization| {
+            let mut model = tiny_model();
+            let config = TrainConfig {
+                epochs: 20,
+                optimizer: OptimizerKind::adam(),
+                learning_rate: 0.01,
+                regularization,
+                verbose: false,
+                ..TrainConfig::default()
+            };
+            let history = train_epochs(&mut model, &data, &[], &config);
+            (history, squared_weights(&model))
+        };
+
+        let (plain, plain_weights) = run(Regularization::default());
+        let initial = squared_weights(&tiny_model());
+        for regularization in [
+            Regularization {
+                l2: 0.5,
+                ..Regularization::default()
+            },
+            Regularization {
+                weight_decay: 0.5,
+                ..Regularization::default()
+            },
+        ] {
+            let (history, weights) = run(regularization);
+            assert!(weights < plain_weights, "{:?}", regularization);
+            // The first batch sees the initial weights in both runs.
+            let penalty = history.batch_losses[0] - plain.batch_losses[0];
+            assert!(
+                (penalty - 0.25 * initial).abs() < 1e-5,
+                "{:?}",
+                regularization
+            );
+        }
+    }
+
+    #[test]
+    fn test_schedule_sets_each_epoch_rate() {
+        let data = vec![(vec![1, 0], 1), (vec![0, 1], 0), (vec![1, 1], 1)];
+        let config = TrainConfig {
+            epochs: 4,
+            learning_rate: 0.4,
+            schedule: LrSchedule::StepDecay {
+                step_size: 1,
+                gamma: 0.5,
+            },
+            verbose: false,
+            ..TrainConfig::default()
+        };
+        let history = train_epochs(&mut tiny_model(), &data, &[], &config);
+        assert_eq!(history.learning_rates, vec![0.4, 0.2, 0.1, 0.05]);
+
+        // A zero rate from the first epoch on leaves the weights alone.
+        let mut model = tiny_model();
+        let frozen = TrainConfig {
+            learning_rate: 0.0,
+            ..config
+        };
+        train_epochs(&mut model, &data, &[], &frozen);
+        assert_eq!(
+            crate::storage::model_to_string(&model),
+            crate::storage::model_to_string(&tiny_model())
+        );
+    }
+
+    #[test]
+    fn test_logit_training_ends_with_a_sigmoid() {
+        use crate::spec::{model_spec, parse_spec};
+
+        let data = vec![(vec![1, 0], 1), (vec![0, 1], 0), (vec![1, 1], 1)];
+        let logits = Loss::BinaryCrossEntropy {
+            weights: ClassWeights::UNIFORM,
+            from_logits: true,
+        };
+        let mut model = parse_spec("2-d4-tanh-d1", 3).unwrap();
+        let config = TrainConfig {
+            epochs: 30,
+            loss: logits,
+            verbose: false,
+            ..TrainConfig::default()
+        };
+        let history = train_epochs(&mut model, &data, &[], &config);
+        assert!(history.epoch_losses.last().unwrap() < history.epoch_losses.first().unwrap());
+
+        assert!(append_logit_sigmoid(&mut model, logits));
+        assert_eq!(model_spec(&model), "2-d4-tanh-d1-sigmoid");
+        let (input, _) = batch_to_matrices(&data, 1);
+        let output = model.predict(&input);
+        assert!(output.as_slice().iter().all(|&p| (0.0..=1.0).contains(&p)));
+        // Already a probability, and other losses never add one.
+        assert!(!append_logit_sigmoid(&mut model, logits));
+        assert!(!append_logit_sigmoid(
+            &mut parse_spec("2-d1", 3).unwrap(),
+            Loss::MeanSquaredError
+        ));
+    }
+
+    #[test]
+    fn test_one_hot_targets() {
+        let data = vec![(vec![1, 0], 2), (vec![0, 1], 0)];
+        let (_, target) = batch_to_matrices(&data, 3);
+        assert_eq!(target.row(0), &[0.0, 0.0, 1.0]);
+        assert_eq!(target.row(1), &[1.0, 0.0, 0.0]);
+        assert_eq!(argmax(&[0.1, 0.7, 0.2]), 1);
+    }
+
+    #[test]
+    fn test_softmax_cross_entropy_learns_classes() {
+        let mut model = Model::new();
+        model.add_layer(LayerEnum::Dense(DenseLayer::from_parts(
+            Matrix::from_vec(vec![vec![0.1, -0.2, 0.3], vec![-0.1, 0.2, 0.1]]),
+            Matrix::new(1, 3),
+        )));
+        model.add_layer(LayerEnum::Softmax(SoftmaxLayer::new()));
+
+        let data = vec![(vec![1, 0], 0), (vec![0, 1], 1), (vec![0, 0], 2)];
+        let config = TrainConfig {
+            epochs: 200,
+            learning_rate: 0.5,
+            loss: Loss::CategoricalCrossEntropy,
+            ..TrainConfig::default()
+        };
+        let history = train_epochs(&mut model, &data, &data, &config);
+        assert_eq!(history.validation_losses.len(), 200);
+        assert!(history.epoch_losses.last().unwrap() < &0.2);
+
+        for (x, y) in &data {
+            let (input, _) = batch_to_matrices(std::iter::once(&(x.clone(), *y)), 3);
+            assert_eq!(argmax(model.predict(&input).row(0)), *y as usize);
+        }
+    }
 }